@@ -0,0 +1,11 @@
+ALTER TABLE budget.records ADD COLUMN card TEXT;
+
+UPDATE budget.records AS r
+SET card = a.name
+FROM budget.accounts AS a
+WHERE a.id = r.account_id;
+
+ALTER TABLE budget.records ALTER COLUMN card SET NOT NULL;
+ALTER TABLE budget.records DROP COLUMN account_id;
+
+DROP TABLE budget.accounts;
@@ -0,0 +1,24 @@
+CREATE TABLE budget.accounts (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    account_type TEXT,
+    owner TEXT,
+    last_four TEXT,
+    credit_limit DOUBLE PRECISION,
+    closing_day SMALLINT CHECK (closing_day BETWEEN 1 AND 31),
+    event_time TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Every card string already in use becomes an account so existing records keep their owner
+INSERT INTO budget.accounts (name)
+SELECT DISTINCT card FROM budget.records;
+
+ALTER TABLE budget.records ADD COLUMN account_id INTEGER REFERENCES budget.accounts (id);
+
+UPDATE budget.records AS r
+SET account_id = a.id
+FROM budget.accounts AS a
+WHERE a.name = r.card;
+
+ALTER TABLE budget.records ALTER COLUMN account_id SET NOT NULL;
+ALTER TABLE budget.records DROP COLUMN card;
@@ -0,0 +1,3 @@
+ALTER TABLE budget.description_information DROP COLUMN category_id;
+
+DROP TABLE budget.categories;
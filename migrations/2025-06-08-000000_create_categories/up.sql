@@ -0,0 +1,13 @@
+CREATE TABLE budget.categories (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    parent_id INTEGER REFERENCES budget.categories (id) ON DELETE CASCADE,
+    event_time TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Sibling names are unique regardless of case so "Groceries" and "groceries" can't both exist
+CREATE UNIQUE INDEX categories_parent_name_idx
+    ON budget.categories (COALESCE(parent_id, 0), lower(name));
+
+ALTER TABLE budget.description_information
+    ADD COLUMN category_id INTEGER REFERENCES budget.categories (id) ON DELETE SET NULL;
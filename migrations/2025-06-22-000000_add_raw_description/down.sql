@@ -0,0 +1 @@
+ALTER TABLE budget.records DROP COLUMN raw_description;
@@ -0,0 +1,3 @@
+-- The description exactly as the bank wrote it, so records can be re-standardized when the rules change.
+-- Records imported before this column existed only have their standardized description.
+ALTER TABLE budget.records ADD COLUMN raw_description TEXT;
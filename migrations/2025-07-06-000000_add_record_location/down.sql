@@ -0,0 +1,4 @@
+ALTER TABLE budget.records
+    DROP COLUMN city,
+    DROP COLUMN state,
+    DROP COLUMN country;
@@ -0,0 +1,5 @@
+-- Where a card transaction was made, parsed from the end of the bank description when present
+ALTER TABLE budget.records
+    ADD COLUMN city TEXT,
+    ADD COLUMN state TEXT,
+    ADD COLUMN country TEXT;
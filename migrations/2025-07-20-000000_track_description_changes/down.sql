@@ -0,0 +1,2 @@
+DROP TRIGGER description_information_touch_event_time ON budget.description_information;
+DROP FUNCTION budget.touch_event_time();
@@ -0,0 +1,13 @@
+-- event_time records when a description's information was last changed, not only when it was first inserted
+ALTER TABLE budget.description_information ALTER COLUMN event_time SET DEFAULT now();
+
+CREATE FUNCTION budget.touch_event_time() RETURNS trigger AS $$
+BEGIN
+    NEW.event_time = now();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER description_information_touch_event_time
+    BEFORE UPDATE ON budget.description_information
+    FOR EACH ROW EXECUTE FUNCTION budget.touch_event_time();
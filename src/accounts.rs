@@ -0,0 +1,503 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use diesel::PgConnection;
+
+use crate::{
+    completion::Completer,
+    database::{
+        db,
+        models::{Account, NewAccount},
+    },
+    description_input_parser,
+};
+
+// A rule mapping a budget file onto a registered account. Every condition that is set must match.
+#[derive(Debug)]
+pub struct AccountRule {
+    origin: String,
+    file_name: Option<String>,
+    last_four: Option<String>,
+    account: String,
+}
+
+impl AccountRule {
+    fn matches(&self, origin: &str, path: &Path, last_four: Option<&str>) -> bool {
+        if !self.origin.eq_ignore_ascii_case(origin) {
+            return false;
+        }
+
+        if let Some(file_name) = &self.file_name {
+            let actual = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !actual.contains(&file_name.to_lowercase()) {
+                return false;
+            }
+        }
+
+        match (&self.last_four, last_four) {
+            (Some(expected), Some(actual)) => expected == actual,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+
+    // Rules with more conditions win over broader ones, so a card specific rule beats an origin wide default
+    fn specificity(&self) -> usize {
+        usize::from(self.file_name.is_some()) + usize::from(self.last_four.is_some())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut rule = serde_json::json!({
+            "origin": self.origin,
+            "account": self.account,
+        });
+        if let Some(file_name) = &self.file_name {
+            rule["file_name"] = file_name.as_str().into();
+        }
+        if let Some(last_four) = &self.last_four {
+            rule["last_four"] = last_four.as_str().into();
+        }
+        rule
+    }
+}
+
+fn optional_string(value: &serde_json::Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty()).map(String::from)
+}
+
+pub fn load_account_rules(path: &Path) -> Result<Vec<AccountRule>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let config: serde_json::Value = serde_json::from_reader(File::open(path)?)?;
+    let Some(raw_rules) = config["rules"].as_array() else {
+        return Err(format!("Account rules file {:?} must contain a 'rules' array", path).into());
+    };
+
+    let mut rules = Vec::with_capacity(raw_rules.len());
+    for raw_rule in raw_rules {
+        let (Some(origin), Some(account)) = (
+            optional_string(&raw_rule["origin"]),
+            optional_string(&raw_rule["account"]),
+        ) else {
+            return Err(format!(
+                "Every account rule in {:?} needs an 'origin' and an 'account'. Rule: {raw_rule}",
+                path
+            )
+            .into());
+        };
+
+        rules.push(AccountRule {
+            origin,
+            file_name: optional_string(&raw_rule["file_name"]),
+            last_four: optional_string(&raw_rule["last_four"]),
+            account,
+        });
+    }
+
+    Ok(rules)
+}
+
+// The origin and last four digits of a card
+type CardKey = (String, Option<String>);
+
+// Resolves the account behind every budget file, registering new accounts as they are found
+pub struct AccountResolver<'a> {
+    connection: &'a mut PgConnection,
+    rules_path: PathBuf,
+    rules: Vec<AccountRule>,
+    accounts: Vec<Account>,
+    cache: HashMap<(String, PathBuf, Option<String>), Option<i32>>,
+    // Whether unknown accounts can be registered through prompts, i.e. there is someone to ask
+    interactive: bool,
+    // Negative ids standing in for unknown accounts during a dry run, with the name they are shown under
+    placeholders: Option<HashMap<CardKey, (i32, String)>>,
+}
+
+impl<'a> AccountResolver<'a> {
+    pub fn new(
+        connection: &'a mut PgConnection,
+        rules_path: PathBuf,
+        interactive: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let rules = load_account_rules(&rules_path)?;
+        let accounts = db::select_accounts(connection)?;
+        Ok(AccountResolver {
+            connection,
+            rules_path,
+            rules,
+            accounts,
+            cache: HashMap::new(),
+            interactive,
+            placeholders: None,
+        })
+    }
+
+    /// Resolve unknown accounts to placeholder ids instead of leaving their records out, so a dry
+    /// run can still show them. The placeholders are never stored.
+    pub fn use_placeholders(&mut self) {
+        self.placeholders = Some(HashMap::new());
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// The name of every registered account and placeholder, by id
+    pub fn account_names(&self) -> HashMap<i32, String> {
+        let placeholders = self
+            .placeholders
+            .iter()
+            .flat_map(|placeholders| placeholders.values().map(|(id, name)| (*id, name.clone())));
+        self.accounts
+            .iter()
+            .map(|account| (account.id, account.name.clone()))
+            .chain(placeholders)
+            .collect()
+    }
+
+    pub fn resolve(&mut self, origin: &str, path: &Path, last_four: Option<&str>) -> Option<i32> {
+        let key = (
+            origin.to_string(),
+            path.to_path_buf(),
+            last_four.map(String::from),
+        );
+        if let Some(account_id) = self.cache.get(&key) {
+            return *account_id;
+        }
+
+        let account_id = self.lookup(origin, path, last_four);
+        self.cache.insert(key, account_id);
+        account_id
+    }
+
+    fn lookup(&mut self, origin: &str, path: &Path, last_four: Option<&str>) -> Option<i32> {
+        let rule = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(origin, path, last_four))
+            .max_by_key(|rule| rule.specificity());
+
+        if let Some(rule) = rule {
+            if let Some(account) = self.find_account(&rule.account) {
+                return Some(account.id);
+            }
+            let suggested_name = rule.account.clone();
+            return self.register(origin, path, last_four, Some(suggested_name));
+        }
+
+        // Without a rule, a registered card with the same last four digits is still an unambiguous match
+        if let Some(last_four) = last_four {
+            let mut matching = self
+                .accounts
+                .iter()
+                .filter(|account| account.last_four.as_deref() == Some(last_four));
+            if let (Some(account), None) = (matching.next(), matching.next()) {
+                return Some(account.id);
+            }
+        }
+
+        self.register(origin, path, last_four, None)
+    }
+
+    fn find_account(&self, name: &str) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|account| account.name.eq_ignore_ascii_case(name))
+    }
+
+    fn register(
+        &mut self,
+        origin: &str,
+        path: &Path,
+        last_four: Option<&str>,
+        suggested_name: Option<String>,
+    ) -> Option<i32> {
+        println!(
+            "No registered account matches {origin} file {:?}{}.",
+            path,
+            last_four
+                .map(|digits| format!(" (card ending {digits})"))
+                .unwrap_or_default()
+        );
+        if !self.interactive {
+            return self.placeholder(origin, last_four);
+        }
+        println!("Enter an existing account name to reuse it, or new details to register one.");
+        println!("Press CRTL + S on the name to skip this account. Files in new/ are then kept whole until it is registered, other files are imported without its records.");
+        println!("On the other details, CRTL + S leaves them blank.");
+
+        enable_raw_mode().unwrap();
+        let account_id = self.register_prompts(origin, path, last_four, suggested_name);
+        disable_raw_mode().unwrap();
+        account_id
+    }
+
+    // The same unknown card gets the same placeholder across files, so duplicates between them show
+    fn placeholder(&mut self, origin: &str, last_four: Option<&str>) -> Option<i32> {
+        let placeholders = self.placeholders.as_mut()?;
+        let next_id = -(placeholders.len() as i32) - 1;
+        let (id, _) = placeholders
+            .entry((origin.to_string(), last_four.map(String::from)))
+            .or_insert_with(|| {
+                let card = last_four
+                    .map(|digits| format!(" ending {digits}"))
+                    .unwrap_or_default();
+                (next_id, format!("{origin}{card} (unregistered)"))
+            });
+        Some(*id)
+    }
+
+    fn register_prompts(
+        &mut self,
+        origin: &str,
+        path: &Path,
+        last_four: Option<&str>,
+        suggested_name: Option<String>,
+    ) -> Option<i32> {
+        let name = loop {
+            match &suggested_name {
+                Some(name) => println!("Account name (Enter to use '{name}'):\r"),
+                None => println!("Account name:\r"),
+            }
+            let names = Completer::new(self.accounts.iter().map(|account| account.name.as_str()));
+            let name = prompt("account name", Some(&names))?;
+            match (name.trim(), &suggested_name) {
+                ("", Some(suggested)) => break suggested.clone(),
+                ("", None) => continue,
+                (name, _) => break name.to_string(),
+            }
+        };
+
+        if let Some(account) = self.find_account(&name) {
+            let account_id = account.id;
+            self.remember(origin, path, last_four, &name);
+            return Some(account_id);
+        }
+
+        println!("Account type (e.g. checking, credit):\r");
+        let types = Completer::new(
+            self.accounts
+                .iter()
+                .filter_map(|account| account.account_type.as_deref()),
+        );
+        let account_type = optional_prompt("account type", Some(&types))?;
+        println!("Owner:\r");
+        let owners = Completer::new(
+            self.accounts
+                .iter()
+                .filter_map(|account| account.owner.as_deref()),
+        );
+        let owner = optional_prompt("owner", Some(&owners))?;
+        let card_digits = match last_four {
+            Some(digits) => Some(digits.to_string()),
+            None => {
+                println!("Last four digits:\r");
+                Some(optional_prompt("last four", None)?)
+            }
+        };
+        let credit_limit = loop {
+            println!("Credit limit:\r");
+            let input = optional_prompt("credit limit", None)?;
+            match input.trim() {
+                "" => break None,
+                limit => match limit.parse::<f64>() {
+                    Ok(limit) => break Some(limit),
+                    Err(_) => println!("'{limit}' is not a valid amount.\r"),
+                },
+            }
+        };
+        let closing_day = loop {
+            println!("Statement closing day (1-31):\r");
+            let input = optional_prompt("closing day", None)?;
+            match input.trim() {
+                "" => break None,
+                day => match day.parse::<i16>() {
+                    Ok(day) if (1..=31).contains(&day) => break Some(day),
+                    _ => println!("'{day}' is not a day of the month.\r"),
+                },
+            }
+        };
+
+        let new_account = NewAccount {
+            name: &name,
+            account_type: non_empty(&account_type),
+            owner: non_empty(&owner),
+            last_four: card_digits.as_deref().and_then(non_empty),
+            credit_limit,
+            closing_day,
+        };
+        let account = match db::insert_account(self.connection, &new_account) {
+            Ok(account) => account,
+            Err(e) => {
+                println!("There was an error registering account '{name}'. Error: {e}\r");
+                return None;
+            }
+        };
+        println!("Registered account '{}' ({:?})\r", account.name, path);
+
+        let account_id = account.id;
+        self.accounts.push(account);
+        self.remember(origin, path, last_four, &name);
+        Some(account_id)
+    }
+
+    // Store the chosen account so the next import of the same file type resolves silently
+    fn remember(&mut self, origin: &str, path: &Path, last_four: Option<&str>, account: &str) {
+        if !point_rules(&mut self.rules, origin, path, last_four, account) {
+            return;
+        }
+
+        let rules: Vec<serde_json::Value> = self.rules.iter().map(AccountRule::to_json).collect();
+        let config = serde_json::json!({ "rules": rules });
+        let result = serde_json::to_string_pretty(&config)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|contents| fs::write(&self.rules_path, contents + "\n").map_err(Into::into));
+        if let Err(e) = result {
+            println!(
+                "Failed to save account rule to {:?}. Error: {e}\r",
+                self.rules_path
+            );
+        }
+    }
+}
+
+// Make the rules resolve the file to `account`, returning whether they changed. The rule that
+// matched the file is pointed at the account, unless that rule is broader than one for this card,
+// in which case a card specific rule is added instead. `last_four` is the file's own, a rule on
+// digits the file doesn't carry could never match it again.
+fn point_rules(
+    rules: &mut Vec<AccountRule>,
+    origin: &str,
+    path: &Path,
+    last_four: Option<&str>,
+    account: &str,
+) -> bool {
+    let specificity = usize::from(last_four.is_some());
+    let matched = rules
+        .iter_mut()
+        .filter(|rule| rule.matches(origin, path, last_four))
+        .max_by_key(|rule| rule.specificity());
+    match matched {
+        Some(rule) if rule.account.eq_ignore_ascii_case(account) => return false,
+        Some(rule) if rule.specificity() >= specificity => rule.account = account.to_string(),
+        _ => rules.push(AccountRule {
+            origin: origin.to_string(),
+            file_name: None,
+            last_four: last_four.map(String::from),
+            account: account.to_string(),
+        }),
+    }
+    true
+}
+
+fn prompt(field: &str, completer: Option<&Completer>) -> Option<String> {
+    match description_input_parser(field, completer) {
+        Some(s) if s == "CRTL+A_ABORT" => None,
+        other => other,
+    }
+}
+
+// Ctrl+S leaves an optional detail blank, only Ctrl+A still gives up on the account
+fn optional_prompt(field: &str, completer: Option<&Completer>) -> Option<String> {
+    match description_input_parser(field, completer) {
+        None => Some(String::new()),
+        Some(s) if s == "CRTL+A_ABORT" => None,
+        Some(s) => Some(s),
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(origin: &str, last_four: Option<&str>, account: &str) -> AccountRule {
+        AccountRule {
+            origin: origin.to_string(),
+            file_name: None,
+            last_four: last_four.map(String::from),
+            account: account.to_string(),
+        }
+    }
+
+    fn resolve<'a>(rules: &'a [AccountRule], origin: &str, last_four: Option<&str>) -> &'a str {
+        rules
+            .iter()
+            .filter(|rule| rule.matches(origin, Path::new("new/bk_download.csv"), last_four))
+            .max_by_key(|rule| rule.specificity())
+            .map(|rule| rule.account.as_str())
+            .unwrap()
+    }
+
+    #[test]
+    fn another_name_replaces_the_origin_rule() {
+        let mut rules = vec![
+            rule("usaa", None, "USAA"),
+            rule("capitalone", None, "CapitalOne"),
+        ];
+        let path = Path::new("new/bk_download.csv");
+        assert!(point_rules(&mut rules, "usaa", path, None, "Checking"));
+        assert_eq!(rules.len(), 2);
+        assert_eq!(resolve(&rules, "usaa", None), "Checking");
+    }
+
+    #[test]
+    fn the_same_name_changes_nothing() {
+        let mut rules = vec![rule("usaa", None, "USAA")];
+        let path = Path::new("new/bk_download.csv");
+        assert!(!point_rules(&mut rules, "usaa", path, None, "usaa"));
+        assert_eq!(rules.len(), 1);
+    }
+
+    #[test]
+    fn a_card_gets_its_own_rule_next_to_the_origin_rule() {
+        let mut rules = vec![rule("capitalone", None, "CapitalOne")];
+        let path = Path::new("new/transactions.csv");
+        assert!(point_rules(
+            &mut rules,
+            "capitalone",
+            path,
+            Some("1234"),
+            "Venture"
+        ));
+        assert_eq!(resolve(&rules, "capitalone", Some("1234")), "Venture");
+        assert_eq!(resolve(&rules, "capitalone", Some("9876")), "CapitalOne");
+    }
+
+    #[test]
+    fn a_card_rule_is_updated_in_place() {
+        let mut rules = vec![rule("capitalone", Some("1234"), "Venture")];
+        let path = Path::new("new/transactions.csv");
+        assert!(point_rules(
+            &mut rules,
+            "capitalone",
+            path,
+            Some("1234"),
+            "Quicksilver"
+        ));
+        assert_eq!(rules.len(), 1);
+        assert_eq!(resolve(&rules, "capitalone", Some("1234")), "Quicksilver");
+    }
+
+    #[test]
+    fn files_without_digits_never_get_a_card_rule() {
+        let mut rules = Vec::new();
+        let path = Path::new("new/bk_download.csv");
+        assert!(point_rules(&mut rules, "usaa", path, None, "Checking"));
+        assert_eq!(rules[0].last_four, None);
+        assert_eq!(resolve(&rules, "usaa", None), "Checking");
+    }
+}
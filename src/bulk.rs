@@ -0,0 +1,288 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    path::Path,
+};
+
+use diesel::PgConnection;
+
+use crate::{
+    categories::{self, CategoryTree},
+    completion::Completer,
+    confirm,
+    database::{db, models::DescriptionSpend},
+    tagging, UploadDescription,
+};
+
+// The information columns are read back on import, the spend columns are only there for context
+const INFORMATION_COLUMNS: [&str; 5] = [
+    "description",
+    "primary_information",
+    "secondary_information",
+    "tertiary_information",
+    "additional_information",
+];
+const SPEND_COLUMNS: [&str; 7] = [
+    "transactions",
+    "total",
+    "average",
+    "first_date",
+    "last_date",
+    "accounts",
+    "raw_descriptions",
+];
+
+fn load_spend(connection: &mut PgConnection) -> HashMap<String, DescriptionSpend> {
+    match db::select_description_spend(connection) {
+        Ok(spend) => spend,
+        Err(e) => panic!("There was an error loading the spend per description. Error: {e}"),
+    }
+}
+
+fn information(description: &UploadDescription) -> [&str; 4] {
+    [
+        &description.primary_information,
+        &description.secondary_information,
+        &description.tertiary_information,
+        &description.additional_information,
+    ]
+}
+
+fn write_descriptions(
+    path: &Path,
+    descriptions: &[UploadDescription],
+    spend: &HashMap<String, DescriptionSpend>,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(INFORMATION_COLUMNS.iter().chain(SPEND_COLUMNS.iter()))?;
+    for description in descriptions {
+        let mut row: Vec<String> = vec![description.description.clone()];
+        row.extend(information(description).map(String::from));
+        match spend.get(&description.description) {
+            Some(spend) => row.extend([
+                spend.transactions.to_string(),
+                format!("{:.2}", spend.total),
+                format!("{:.2}", spend.average),
+                spend.first_date.to_string(),
+                spend.last_date.to_string(),
+                spend.accounts.join(", "),
+                spend.raw_descriptions.join(" | "),
+            ]),
+            None => row.extend(SPEND_COLUMNS.iter().map(|_| String::new())),
+        }
+        writer.write_record(&row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Export the untagged descriptions, or every description with `all`, to a CSV file to be filled
+/// in with a spreadsheet. Descriptions are listed by spend, largest spending first.
+pub fn export_descriptions(connection: &mut PgConnection, path: &Path, all: bool) {
+    let untagged = match db::select_untagged_descriptions(connection) {
+        Ok(untagged) => untagged,
+        Err(e) => panic!("There was an error loading the untagged descriptions. Error: {e}"),
+    };
+    let mut descriptions: Vec<UploadDescription> = untagged
+        .into_iter()
+        .map(|description| UploadDescription {
+            description,
+            ..Default::default()
+        })
+        .collect();
+    if all {
+        descriptions.extend(
+            db::select_descriptions(connection)
+                .iter()
+                .map(UploadDescription::from),
+        );
+    }
+
+    let spend = load_spend(connection);
+    tagging::sort_by_spend(&mut descriptions, &spend);
+
+    match write_descriptions(path, &descriptions, &spend) {
+        Ok(_) => println!(
+            "Exported {} description(s) to {:?}. Fill in the information columns and run 'budget descriptions import {}'.",
+            descriptions.len(),
+            path,
+            path.display()
+        ),
+        Err(e) => panic!("There was an error writing the descriptions to {:?}. Error: {e}", path),
+    }
+}
+
+// Read the filled in rows, keyed by their line in the file. Rows without any information are left out.
+fn read_descriptions(path: &Path) -> Result<Vec<(u64, UploadDescription)>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let mut positions = Vec::new();
+    for column in INFORMATION_COLUMNS {
+        match headers.iter().position(|header| header.trim() == column) {
+            Some(position) => positions.push(position),
+            None => return Err(format!("the file has no '{column}' column").into()),
+        }
+    }
+
+    let mut descriptions = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let line = row.position().map_or(0, |position| position.line());
+        let value = |index: usize| row.get(positions[index]).unwrap_or("").trim().to_string();
+        let description = UploadDescription {
+            description: value(0),
+            primary_information: value(1),
+            secondary_information: value(2),
+            tertiary_information: value(3),
+            additional_information: value(4),
+            category_id: None,
+        };
+        if information(&description)
+            .iter()
+            .all(|field| field.is_empty())
+        {
+            continue;
+        }
+        descriptions.push((line, description));
+    }
+    Ok(descriptions)
+}
+
+// Everything wrong with the filled in rows. Nothing is imported unless this is empty.
+fn validate(rows: &[(u64, UploadDescription)], known: &HashSet<String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut seen: HashMap<&str, u64> = HashMap::new();
+    for (line, row) in rows {
+        let name = &row.description;
+        if name.is_empty() {
+            errors.push(format!("line {line}: the description is empty"));
+            continue;
+        }
+        if !known.contains(name) {
+            errors.push(format!(
+                "line {line}: '{name}' is not a description in the records"
+            ));
+        }
+        if let Some(first) = seen.insert(name, *line) {
+            errors.push(format!(
+                "line {line}: '{name}' is already filled in on line {first}"
+            ));
+        }
+
+        // Each level of information needs the one above it
+        let fields = information(row);
+        if fields[0].is_empty() {
+            errors.push(format!(
+                "line {line}: '{name}' has information but no primary information"
+            ));
+        } else if fields[1].is_empty() && !fields[2].is_empty() {
+            errors.push(format!(
+                "line {line}: '{name}' has tertiary information but no secondary information"
+            ));
+        }
+    }
+    errors
+}
+
+/// Import description information filled in from an export. The file is validated first and the
+/// changes are shown as a diff against the stored information before anything is saved.
+pub fn import_descriptions(connection: &mut PgConnection, path: &Path) {
+    let mut rows = match read_descriptions(path) {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("Could not read descriptions from {:?}. Error: {e}", path);
+            std::process::exit(1);
+        }
+    };
+
+    let stored: HashMap<String, UploadDescription> = db::select_descriptions(connection)
+        .iter()
+        .map(|description| {
+            let description = UploadDescription::from(description);
+            (description.description.clone(), description)
+        })
+        .collect();
+    let mut known: HashSet<String> = load_spend(connection).into_keys().collect();
+    known.extend(stored.keys().cloned());
+
+    let errors = validate(&rows, &known);
+    if !errors.is_empty() {
+        println!("{:?} has {} problem(s):", path, errors.len());
+        for error in &errors {
+            println!("  {error}");
+        }
+        println!("Nothing was imported.");
+        std::process::exit(1);
+    }
+
+    // Compare what would be stored, with every existing category in its stored spelling, so
+    // case and spacing edits don't show up as changes
+    let tree = CategoryTree::load(connection);
+    for (_, row) in &mut rows {
+        let canonical = tree.canonical(&[
+            &row.primary_information,
+            &row.secondary_information,
+            &row.tertiary_information,
+        ]);
+        let fields = [
+            &mut row.primary_information,
+            &mut row.secondary_information,
+            &mut row.tertiary_information,
+        ];
+        for (field, name) in fields.into_iter().zip(canonical) {
+            *field = name;
+        }
+    }
+
+    // Values that are probably another spelling of one already in use
+    let completers: Vec<Completer> = (0..4)
+        .map(|index| {
+            Completer::new(
+                stored
+                    .values()
+                    .map(|description| information(description)[index]),
+            )
+        })
+        .collect();
+
+    let mut changed: Vec<UploadDescription> = Vec::new();
+    let (mut added, mut updated) = (0, 0);
+    for (_, row) in rows {
+        let new = information(&row).join(" > ");
+        match stored.get(&row.description) {
+            Some(existing) if information(existing) == information(&row) => continue,
+            Some(existing) => {
+                updated += 1;
+                println!("~ {}", row.description);
+                println!("    - {}", information(existing).join(" > "));
+                println!("    + {new}");
+            }
+            None => {
+                added += 1;
+                println!("+ {}: {new}", row.description);
+            }
+        }
+        for (field, completer) in information(&row).iter().zip(&completers) {
+            if let Some(existing) = completer.close_to(field) {
+                println!("    warning: '{field}' is close to the existing '{existing}'");
+            }
+        }
+        changed.push(row);
+    }
+
+    if changed.is_empty() {
+        println!("The file matches the stored information. Nothing to import.");
+        return;
+    }
+    println!("{added} description(s) to add and {updated} to update.");
+    if !confirm("Import these changes?") {
+        println!("Nothing was imported.");
+        return;
+    }
+
+    categories::link_descriptions(connection, &mut changed);
+    match db::insert_description(connection, &changed) {
+        Ok(count) => println!("Imported {count} description(s)."),
+        Err(e) => panic!("There was an error importing the description information. Error: {e}"),
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use diesel::{Connection, PgConnection, QueryResult};
+
+use crate::{
+    database::{
+        db,
+        models::{Category, NewCategory},
+    },
+    description_input_parser, UploadDescription,
+};
+
+const LEVELS: [&str; 3] = ["primary", "secondary", "tertiary"];
+
+// Comparison key for category names, so case, spacing and simple plurals don't create new categories
+pub fn category_key(name: &str) -> String {
+    name.to_lowercase()
+        .split_whitespace()
+        .map(|word| {
+            let word: String = word
+                .chars()
+                .filter(|c| c.is_alphanumeric() || *c == '&')
+                .collect();
+            if word.len() > 4 && word.ends_with("ies") {
+                format!("{}y", &word[..word.len() - 3])
+            } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+                word[..word.len() - 1].to_string()
+            } else {
+                word
+            }
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub struct CategoryTree {
+    categories: Vec<Category>,
+}
+
+impl CategoryTree {
+    pub fn load(connection: &mut PgConnection) -> Self {
+        CategoryTree {
+            categories: db::select_categories(connection),
+        }
+    }
+
+    fn find(&self, parent_id: Option<i32>, name: &str) -> Option<&Category> {
+        let key = category_key(name);
+        self.categories
+            .iter()
+            .find(|category| category.parent_id == parent_id && category_key(&category.name) == key)
+    }
+
+    // Walk a path of names through the tree without creating anything. Some(None) is the root.
+    fn find_path(&self, names: &[String]) -> Option<Option<i32>> {
+        let mut parent_id = None;
+        for name in names {
+            parent_id = Some(self.find(parent_id, name)?.id);
+        }
+        Some(parent_id)
+    }
+
+    /// Names from the root down to the category
+    pub fn path(&self, id: i32) -> Vec<String> {
+        let mut path = Vec::new();
+        let mut current = Some(id);
+        while let Some(category) =
+            current.and_then(|id| self.categories.iter().find(|category| category.id == id))
+        {
+            path.push(category.name.clone());
+            current = category.parent_id;
+        }
+        path.reverse();
+        path
+    }
+
+    /// The stored spelling of every level of `names` that already exists, stopping at the first
+    /// empty name. Levels that would be new categories keep their trimmed spelling.
+    pub fn canonical(&self, names: &[&str]) -> Vec<String> {
+        let mut parent_id: Option<i32> = None;
+        // Below a new category nothing exists yet
+        let mut existing = true;
+        let mut canonical: Vec<String> = Vec::new();
+
+        for name in names.iter().map(|name| name.trim()) {
+            if name.is_empty() {
+                break;
+            }
+
+            match self.find(parent_id, name).filter(|_| existing) {
+                Some(category) => {
+                    parent_id = Some(category.id);
+                    canonical.push(category.name.clone());
+                }
+                None => {
+                    existing = false;
+                    canonical.push(name.to_string());
+                }
+            }
+        }
+        canonical
+    }
+
+    /// Find or create the category at the end of `names`, stopping at the first empty name.
+    /// Returns the deepest category id along with the stored spelling of every level.
+    pub fn resolve(
+        &mut self,
+        connection: &mut PgConnection,
+        names: &[&str],
+    ) -> QueryResult<Option<(i32, Vec<String>)>> {
+        let mut parent_id: Option<i32> = None;
+        let mut canonical: Vec<String> = Vec::new();
+
+        for name in names.iter().map(|name| name.trim()) {
+            if name.is_empty() {
+                break;
+            }
+
+            let category = match self.find(parent_id, name) {
+                Some(category) => category.clone(),
+                None => {
+                    let category =
+                        db::insert_category(connection, &NewCategory { name, parent_id })?;
+                    self.categories.push(category.clone());
+                    category
+                }
+            };
+            parent_id = Some(category.id);
+            canonical.push(category.name);
+        }
+
+        Ok(parent_id.map(|id| (id, canonical)))
+    }
+}
+
+/// Link descriptions into the category tree, creating categories as needed. The information
+/// fields are rewritten to the stored spelling of each category.
+pub fn link_descriptions(connection: &mut PgConnection, descriptions: &mut [UploadDescription]) {
+    let mut tree = CategoryTree::load(connection);
+    for description in descriptions {
+        let names = [
+            description.primary_information.as_str(),
+            description.secondary_information.as_str(),
+            description.tertiary_information.as_str(),
+        ];
+        match tree.resolve(connection, &names) {
+            Ok(Some((category_id, stored))) => {
+                description.category_id = Some(category_id);
+                let fields = [
+                    &mut description.primary_information,
+                    &mut description.secondary_information,
+                    &mut description.tertiary_information,
+                ];
+                for (field, name) in fields.into_iter().zip(stored) {
+                    *field = name;
+                }
+            }
+            Ok(None) => description.category_id = None,
+            Err(e) => println!(
+                "There was an error categorizing description '{}'. Error: {e}",
+                description.description
+            ),
+        }
+    }
+}
+
+// Ask which spelling to keep for a group of similar names. None means the user aborted.
+fn choose_spelling(level: &str, parent: &[String], variants: &[(String, usize)]) -> Option<String> {
+    println!("\r");
+    if parent.is_empty() {
+        println!("Found similar {level} categories:\r");
+    } else {
+        println!(
+            "Found similar {level} categories under '{}':\r",
+            parent.join(" > ")
+        );
+    }
+    for (index, (variant, count)) in variants.iter().enumerate() {
+        println!("  {}) {variant} ({count} description(s))\r", index + 1);
+    }
+    println!("Choose the name to keep, or type a new one. Press Enter for 1.\r");
+
+    loop {
+        let input = description_input_parser("category name", None);
+        let input = match input {
+            Some(s) if s == "CRTL+A_ABORT" => return None,
+            Some(s) => s,
+            None => String::new(), // CRTL+S keeps the most used spelling
+        };
+
+        let input = input.trim();
+        if input.is_empty() {
+            return Some(variants[0].0.clone());
+        }
+        match input.parse::<usize>() {
+            Ok(index) if (1..=variants.len()).contains(&index) => {
+                return Some(variants[index - 1].0.clone())
+            }
+            Ok(_) => println!("Please choose a number between 1 and {}.\r", variants.len()),
+            Err(_) => return Some(input.to_string()),
+        }
+    }
+}
+
+/// Guided migration of the free text primary/secondary/tertiary columns into the category tree.
+/// Similar spellings are grouped level by level and the user picks the one to keep.
+pub fn migrate_descriptions(connection: &mut PgConnection) {
+    let descriptions = db::select_descriptions(connection);
+    let mut tree = CategoryTree::load(connection);
+
+    // Each description's path, cut at the first missing level
+    let mut paths: Vec<Vec<String>> = descriptions
+        .iter()
+        .map(|description| {
+            [
+                &description.primary_information,
+                &description.secondary_information,
+                &description.tertiary_information,
+            ]
+            .into_iter()
+            .map(|name| name.as_deref().unwrap_or("").trim().to_string())
+            .take_while(|name| !name.is_empty())
+            .collect()
+        })
+        .collect();
+
+    println!(
+        "Migrating {} description(s) into the category tree.",
+        descriptions.len()
+    );
+    println!("Press CRTL + S to keep the most used name, and CRTL + A to abort without saving.");
+
+    enable_raw_mode().unwrap();
+    for (level, level_name) in LEVELS.iter().enumerate() {
+        // Group every spelling at this level by its parent path and comparison key
+        let mut groups: BTreeMap<(Vec<String>, String), HashMap<String, usize>> = BTreeMap::new();
+        for path in paths.iter().filter(|path| path.len() > level) {
+            let parent = path[..level].to_vec();
+            let variants = groups
+                .entry((parent, category_key(&path[level])))
+                .or_default();
+            *variants.entry(path[level].clone()).or_default() += 1;
+        }
+
+        let mut canonical: HashMap<(Vec<String>, String), String> = HashMap::new();
+        for ((parent, key), variants) in groups {
+            // A category that already exists is always the first choice
+            let existing = tree.find_path(&parent).and_then(|parent_id| {
+                variants
+                    .keys()
+                    .next()
+                    .and_then(|variant| tree.find(parent_id, variant))
+                    .map(|category| category.name.clone())
+            });
+
+            let mut variants: Vec<(String, usize)> = variants.into_iter().collect();
+            variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if let Some(existing) = existing {
+                let count = variants
+                    .iter()
+                    .find(|(variant, _)| *variant == existing)
+                    .map_or(0, |(_, count)| *count);
+                variants.retain(|(variant, _)| *variant != existing);
+                variants.insert(0, (existing, count));
+            }
+
+            let chosen = if variants.len() == 1 {
+                variants[0].0.clone()
+            } else {
+                match choose_spelling(level_name, &parent, &variants) {
+                    Some(chosen) => chosen,
+                    None => {
+                        disable_raw_mode().unwrap();
+                        println!("Category migration aborted. Nothing was saved.");
+                        return;
+                    }
+                }
+            };
+            canonical.insert((parent, key), chosen);
+        }
+
+        for path in paths.iter_mut().filter(|path| path.len() > level) {
+            let key = (path[..level].to_vec(), category_key(&path[level]));
+            path[level] = canonical[&key].clone();
+        }
+    }
+
+    let distinct_paths: BTreeMap<&Vec<String>, usize> = paths
+        .iter()
+        .filter(|path| !path.is_empty())
+        .fold(BTreeMap::new(), |mut acc, path| {
+            *acc.entry(path).or_default() += 1;
+            acc
+        });
+    println!("\r");
+    println!("Resulting categories:\r");
+    for (path, count) in &distinct_paths {
+        println!("  {} ({count} description(s))\r", path.join(" > "));
+    }
+    let gapped: Vec<&str> = descriptions
+        .iter()
+        .zip(&paths)
+        .filter(|(description, path)| {
+            [
+                &description.primary_information,
+                &description.secondary_information,
+                &description.tertiary_information,
+            ]
+            .into_iter()
+            .skip(path.len())
+            .any(|name| name.as_deref().is_some_and(|name| !name.trim().is_empty()))
+        })
+        .map(|(description, _)| description.description.as_str())
+        .collect();
+    if !gapped.is_empty() {
+        println!("These descriptions skip a level. Only the levels above the gap are linked, the rest is kept as is:\r");
+        for description in &gapped {
+            println!("  {description}\r");
+        }
+    }
+    println!("Save the category tree and link the descriptions? Type 'y' to confirm.\r");
+    let confirmed = matches!(description_input_parser("confirmation", None), Some(s) if s.trim().eq_ignore_ascii_case("y"));
+    disable_raw_mode().unwrap();
+
+    if !confirmed {
+        println!("Category migration cancelled. Nothing was saved.");
+        return;
+    }
+
+    let result = connection.transaction(|connection| {
+        let mut linked = 0;
+        for (description, path) in descriptions.iter().zip(&paths) {
+            let names: Vec<&str> = path.iter().map(String::as_str).collect();
+            let (category_id, stored) = match tree.resolve(connection, &names)? {
+                Some((category_id, stored)) => (Some(category_id), stored),
+                None => (None, Vec::new()),
+            };
+            // Levels below a gap aren't part of the path, they are written back untouched
+            let original = [
+                &description.primary_information,
+                &description.secondary_information,
+                &description.tertiary_information,
+            ];
+            let name = |level: usize| {
+                stored
+                    .get(level)
+                    .map(String::as_str)
+                    .or(original[level].as_deref())
+            };
+            db::update_description_category(
+                connection,
+                &description.description,
+                [name(0), name(1), name(2)],
+                category_id,
+            )?;
+            linked += usize::from(category_id.is_some());
+        }
+        QueryResult::Ok(linked)
+    });
+
+    match result {
+        Ok(linked) => println!(
+            "Linked {linked} description(s) to {} categories.",
+            tree.categories.len()
+        ),
+        Err(e) => panic!("There was an error saving the category tree. Error: {e}"),
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::database::models::Description;
+
+// Character n-grams from 2 to 4 characters long, with word boundaries marked by spaces
+const NGRAM_SIZES: std::ops::RangeInclusive<usize> = 2..=4;
+
+fn ngrams(text: &str) -> Vec<String> {
+    let padded: Vec<char> = format!(
+        " {} ",
+        text.to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    )
+    .chars()
+    .collect();
+
+    let mut ngrams = Vec::new();
+    for size in NGRAM_SIZES {
+        for window in padded.windows(size) {
+            ngrams.push(window.iter().collect());
+        }
+    }
+    ngrams
+}
+
+#[derive(Default)]
+struct LabelCounts {
+    documents: usize,
+    ngrams: HashMap<String, usize>,
+    total_ngrams: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub label: String,
+    // Posterior probability of the label among every known label, from 0 to 1
+    pub confidence: f64,
+}
+
+/// Multinomial naive Bayes over character n-grams. Learning only updates counts,
+/// so new examples can be added at any time without retraining from scratch.
+#[derive(Default)]
+pub struct Classifier {
+    labels: HashMap<String, LabelCounts>,
+    vocabulary: HashSet<String>,
+    documents: usize,
+}
+
+impl Classifier {
+    pub fn learn(&mut self, text: &str, label: &str) {
+        let counts = self.labels.entry(label.to_string()).or_default();
+        counts.documents += 1;
+        for ngram in ngrams(text) {
+            counts.total_ngrams += 1;
+            *counts.ngrams.entry(ngram.clone()).or_default() += 1;
+            self.vocabulary.insert(ngram);
+        }
+        self.documents += 1;
+    }
+
+    pub fn predict(&self, text: &str) -> Option<Prediction> {
+        if self.labels.is_empty() {
+            return None;
+        }
+
+        let ngrams = ngrams(text);
+        let vocabulary = self.vocabulary.len() as f64;
+        let scores: Vec<(&String, f64)> = self
+            .labels
+            .iter()
+            .map(|(label, counts)| {
+                let prior = (counts.documents as f64 / self.documents as f64).ln();
+                // Laplace smoothing so unseen n-grams don't zero out a label
+                let denominator = counts.total_ngrams as f64 + vocabulary;
+                let likelihood: f64 = ngrams
+                    .iter()
+                    .map(|ngram| {
+                        let count = counts.ngrams.get(ngram).copied().unwrap_or(0) as f64;
+                        ((count + 1.0) / denominator).ln()
+                    })
+                    .sum();
+                (label, prior + likelihood)
+            })
+            .collect();
+
+        // Normalize the log scores into probabilities without overflowing
+        let best = scores
+            .iter()
+            .map(|(_, score)| *score)
+            .fold(f64::MIN, f64::max);
+        let total: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+        scores
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(label, score)| Prediction {
+                label: label.clone(),
+                confidence: (score - best).exp() / total,
+            })
+    }
+}
+
+/// Predicts the primary and secondary information of a description from the ones already tagged
+#[derive(Default)]
+pub struct CategoryClassifier {
+    primary: Classifier,
+    // Keyed by the primary information too, so the secondary prediction stays consistent with it
+    secondary: HashMap<String, Classifier>,
+}
+
+impl CategoryClassifier {
+    pub fn train(descriptions: &[Description]) -> Self {
+        let mut classifier = CategoryClassifier::default();
+        for description in descriptions {
+            classifier.learn(
+                &description.description,
+                description.primary_information.as_deref().unwrap_or(""),
+                description.secondary_information.as_deref().unwrap_or(""),
+            );
+        }
+        classifier
+    }
+
+    pub fn learn(&mut self, description: &str, primary: &str, secondary: &str) {
+        let (primary, secondary) = (primary.trim(), secondary.trim());
+        if primary.is_empty() {
+            return;
+        }
+
+        self.primary.learn(description, primary);
+        if !secondary.is_empty() {
+            self.secondary
+                .entry(primary.to_string())
+                .or_default()
+                .learn(description, secondary);
+        }
+    }
+
+    pub fn predict(&self, description: &str) -> (Option<Prediction>, Option<Prediction>) {
+        let primary = self.primary.predict(description);
+        let secondary = primary.as_ref().and_then(|primary| {
+            self.secondary
+                .get(&primary.label)
+                .and_then(|classifier| classifier.predict(description))
+        });
+        (primary, secondary)
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    database::models::RecordFilter,
+    report::Grouping,
+    standardize::{RuleKind, StandardizationRule},
+};
+
+/// Import bank exports, tag their descriptions and report on the spending
+#[derive(Parser)]
+#[command(name = "budget")]
+pub struct Cli {
+    /// Never prompt. Files of unregistered accounts are left in place and new descriptions wait
+    /// for 'budget tag'. Implied when there is no terminal.
+    #[arg(long, global = true)]
+    pub headless: bool,
+
+    /// What to do. Defaults to 'import'.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Import the budget files in new/, or the given files, and tag their new descriptions
+    Import {
+        /// Budget files to import instead of the ones in new/. '-' reads one from stdin.
+        paths: Vec<PathBuf>,
+        /// Read the files as this bank's export instead of detecting it from the header
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// Delete the given files once imported. Files in new/ are always deleted.
+        #[arg(long)]
+        remove: bool,
+        /// Show the records, skipped rows, duplicates and new descriptions the import would
+        /// produce, without changing the database or the files
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Keep importing budget files as they land in new/, leaving their descriptions to 'budget tag'
+    Watch {
+        /// Seconds between checks. Files are imported once unchanged for a whole interval.
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Tag the descriptions that have no information yet
+    Tag,
+    /// Show income and spending totals of the stored records
+    Report {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// What to break the spending down by
+        #[arg(long, value_enum, default_value_t = Grouping::Category)]
+        by: Grouping,
+    },
+    /// Export the stored records with their description information as CSV
+    Export {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List the stored records whose description contains a search term
+    Query {
+        /// Text to look for in the standardized and raw descriptions
+        term: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Show at most this many records
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Merge categories that are spelled differently
+    Categories,
+    /// Add a standardization rule
+    AddRule(RuleArgs),
+    /// Preview a standardization rule against the stored descriptions before adding it
+    TestRule(RuleArgs),
+    /// Re-apply the standardization rules to the stored records
+    Restandardize,
+    /// Search, view, edit and delete description information
+    Descriptions {
+        #[command(subcommand)]
+        command: DescriptionsCommand,
+    },
+}
+
+/// The banks whose exports can be imported
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Usaa,
+    Capitalone,
+}
+
+impl Format {
+    pub fn origin(self) -> &'static str {
+        match self {
+            Format::Usaa => "usaa",
+            Format::Capitalone => "capitalone",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum DescriptionsCommand {
+    /// Show the descriptions matching a search term
+    #[command(alias = "show")]
+    Search { term: String },
+    /// Edit the information of the descriptions matching a search term
+    Edit { term: String },
+    /// Delete the information stored for a description
+    Delete { description: String },
+    /// Export untagged descriptions with their spend to CSV, to tag them in a spreadsheet
+    Export {
+        file: PathBuf,
+        /// Export every description, not only the untagged ones
+        #[arg(long)]
+        all: bool,
+    },
+    /// Import description information from a filled in export
+    Import { file: PathBuf },
+}
+
+/// Which records to include
+#[derive(Args)]
+pub struct FilterArgs {
+    /// First date to include (YYYY-MM-DD)
+    #[arg(long)]
+    pub from: Option<NaiveDate>,
+    /// Last date to include (YYYY-MM-DD)
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+    /// Only records of this account
+    #[arg(long)]
+    pub account: Option<String>,
+}
+
+impl FilterArgs {
+    pub fn to_filter(&self) -> RecordFilter {
+        RecordFilter {
+            from: self.from,
+            to: self.to,
+            account: self.account.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+#[command(
+    after_help = "Regex rules can reference capture groups in the standardized description, e.g. '$1'."
+)]
+pub struct RuleArgs {
+    /// How the pattern is matched against raw descriptions
+    #[arg(long, default_value = "prefix", value_parser = parse_kind)]
+    pub kind: RuleKind,
+    /// Text or regex to look for in raw descriptions
+    pub pattern: String,
+    /// The description matching records are stored under
+    pub standardized: String,
+    /// Rules with a higher priority win over longer matches
+    #[arg(default_value_t = 0, allow_negative_numbers = true)]
+    pub priority: i64,
+}
+
+fn parse_kind(kind: &str) -> Result<RuleKind, String> {
+    RuleKind::parse(kind).ok_or_else(|| format!("'{kind}' is not prefix, contains or regex"))
+}
+
+impl RuleArgs {
+    pub fn into_rule(self) -> StandardizationRule {
+        StandardizationRule {
+            pattern: match self.kind {
+                RuleKind::Regex => self.pattern,
+                _ => self.pattern.to_lowercase(),
+            },
+            kind: self.kind,
+            standardized: self.standardized,
+            priority: self.priority,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::suggestions::bigram_similarity;
+
+// A new value at least this similar to an existing one is most likely another spelling of it
+const CLOSE_SIMILARITY: f64 = 0.7;
+
+/// Values a field has been given before, most used first, to complete and check new input against
+#[derive(Default)]
+pub struct Completer {
+    values: Vec<String>,
+}
+
+impl Completer {
+    pub fn new<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for value in values.into_iter().map(str::trim) {
+            if !value.is_empty() {
+                *counts.entry(value).or_default() += 1;
+            }
+        }
+
+        let mut counted: Vec<(&str, usize)> = counts.into_iter().collect();
+        counted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        Completer {
+            values: counted
+                .into_iter()
+                .map(|(value, _)| value.to_string())
+                .collect(),
+        }
+    }
+
+    /// The most used value that starts with `input`, ignoring case
+    pub fn complete(&self, input: &str) -> Option<&str> {
+        if input.is_empty() {
+            return None;
+        }
+        let lower = input.to_lowercase();
+        self.values
+            .iter()
+            .map(String::as_str)
+            .find(|value| value.to_lowercase().starts_with(&lower) && *value != input)
+    }
+
+    /// The rest of the completion for `input`, to show after what has been typed so far
+    pub fn hint(&self, input: &str) -> Option<String> {
+        self.complete(input)
+            .map(|value| value.chars().skip(input.chars().count()).collect())
+    }
+
+    /// An existing value that `input` is probably meant to be, when it isn't exactly one already
+    pub fn close_to(&self, input: &str) -> Option<&str> {
+        let input = input.trim();
+        if input.is_empty() || self.values.iter().any(|value| value == input) {
+            return None;
+        }
+
+        let lower = input.to_lowercase();
+        // Reversed so the most used value wins a tie
+        self.values
+            .iter()
+            .rev()
+            .map(|value| (bigram_similarity(&lower, &value.to_lowercase()), value))
+            .filter(|(score, _)| *score >= CLOSE_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completer() -> Completer {
+        Completer::new(["Groceries", "Dining", "Groceries", "Travel", " Gifts "])
+    }
+
+    #[test]
+    fn close_to_finds_other_spellings() {
+        assert_eq!(completer().close_to("grocries"), Some("Groceries"));
+        assert_eq!(completer().close_to("dinning"), Some("Dining"));
+    }
+
+    #[test]
+    fn close_to_ignores_exact_and_unrelated_values() {
+        assert_eq!(completer().close_to("Groceries"), None);
+        assert_eq!(completer().close_to(" Travel "), None);
+        assert_eq!(completer().close_to("Utilities"), None);
+        assert_eq!(completer().close_to(""), None);
+    }
+
+    #[test]
+    fn close_to_prefers_the_most_used_value_on_a_tie() {
+        let completer = Completer::new(["Grocery A", "Grocery B", "Grocery B"]);
+        assert_eq!(completer.close_to("Grocery C"), Some("Grocery B"));
+    }
+
+    #[test]
+    fn complete_uses_the_most_used_match() {
+        let completer = Completer::new(["Gifts", "Groceries", "Groceries"]);
+        assert_eq!(completer.complete("g"), Some("Groceries"));
+        assert_eq!(completer.complete("gi"), Some("Gifts"));
+        assert_eq!(completer.complete("Groceries"), None);
+        assert_eq!(completer.hint("Gro"), Some("ceries".to_string()));
+    }
+}
@@ -1,57 +1,494 @@
 use crate::{BudgetRecord, UploadDescription};
 
 use super::models::*;
+use chrono::NaiveDate;
 use diesel::prelude::*;
+use std::{collections::HashMap, env};
 
-fn generate_database_url(secret_config: serde_json::Value) -> String {
-    let url = format!(
-        "postgres://{}:{}@{}:{}/{}",
-        secret_config["database"]["username"],
-        secret_config["database"]["password"],
-        secret_config["database"]["host"],
-        secret_config["database"]["port"],
-        secret_config["database"]["name"]
-    );
-    return url.replace('"', "").to_string();
+// Connection settings read from the "database" section of the secret config, as (libpq keyword, config key)
+const CONNECTION_SETTINGS: [(&str, &str); 8] = [
+    ("service", "service"),
+    ("host", "host"),
+    ("port", "port"),
+    ("dbname", "name"),
+    ("user", "username"),
+    ("password", "password"),
+    ("sslmode", "sslmode"),
+    ("sslrootcert", "sslrootcert"),
+];
+
+// Config values may be written as strings or numbers (e.g. the port). Empty values are left to libpq defaults.
+fn config_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
 }
 
-pub fn establish_connection(secret_config: serde_json::Value) -> PgConnection {
-    let database_url = generate_database_url(secret_config);
-    PgConnection::establish(&database_url).unwrap_or_else(|_| {
-        panic!(
-            "Error connecting to database. Ensure the database URL is valid. URL: {database_url}"
+// Quote a value for a libpq keyword/value connection string, so any character in a password is safe
+fn quote_conninfo_value(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// DATABASE_URL wins over the secret config. Anything left unset falls back to libpq's own
+// environment variables (PGHOST, PGSERVICE, ...) and service file.
+fn generate_connection_string(secret_config: &serde_json::Value) -> String {
+    if let Ok(url) = env::var("DATABASE_URL") {
+        return url;
+    }
+
+    let database = &secret_config["database"];
+    if let Some(service_file) = config_value(&database["service_file"]) {
+        if env::var_os("PGSERVICEFILE").is_none() {
+            env::set_var("PGSERVICEFILE", service_file);
+        }
+    }
+
+    CONNECTION_SETTINGS
+        .iter()
+        .filter_map(|(keyword, key)| {
+            config_value(&database[key])
+                .map(|value| format!("{keyword}={}", quote_conninfo_value(&value)))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// A description of the database being connected to that is safe to print, i.e. without credentials
+fn describe_target(secret_config: &serde_json::Value) -> String {
+    if let Ok(url) = env::var("DATABASE_URL") {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return "from DATABASE_URL".to_string();
+        };
+        let host_and_path = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+        let host_and_path = host_and_path
+            .split_once('?')
+            .map_or(host_and_path, |(h, _)| h);
+        return format!("{scheme}://{host_and_path} (from DATABASE_URL)");
+    }
+
+    let database = &secret_config["database"];
+    if let Some(service) = config_value(&database["service"]) {
+        return format!("service '{service}'");
+    }
+    format!(
+        "{}:{}/{}",
+        config_value(&database["host"]).unwrap_or_default(),
+        config_value(&database["port"]).unwrap_or_default(),
+        config_value(&database["name"]).unwrap_or_default()
+    )
+}
+
+/// Connect to the database, describing the target without credentials when it fails
+pub fn connect(secret_config: &serde_json::Value) -> Result<PgConnection, String> {
+    let connection_string = generate_connection_string(secret_config);
+    PgConnection::establish(&connection_string).map_err(|e| {
+        format!(
+            "Error connecting to database {}. Ensure the database settings are valid. Error: {e}",
+            describe_target(secret_config)
         )
     })
 }
 
-pub fn insert_records<'a>(
+pub fn establish_connection(secret_config: serde_json::Value) -> PgConnection {
+    connect(&secret_config).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Whether the connection still reaches the database, e.g. after a query failed
+pub fn is_connected(connection: &mut PgConnection) -> bool {
+    diesel::sql_query("SELECT 1").execute(connection).is_ok()
+}
+
+// Postgres allows at most 65535 bind parameters per statement and every record binds one per
+// column, so each insert holds as many records as fit under the limit
+const RECORDS_PER_INSERT: usize = u16::MAX as usize / NEW_RECORD_COLUMNS;
+
+/// Insert records in chunks inside a single transaction, so a multi-year import either fully
+/// lands or not at all. `on_progress` is called with the inserted and total counts after each chunk.
+pub fn insert_records(
     connection: &mut PgConnection,
-    records: &'a [BudgetRecord],
+    records: &[BudgetRecord],
+    mut on_progress: impl FnMut(usize, usize),
 ) -> QueryResult<usize> {
     use super::schema::records;
-    let insertable_records: Vec<NewRecord> = records.iter().map(|r| r.into()).collect();
-    diesel::insert_into(records::table)
-        .values(&insertable_records)
-        .execute(connection)
+    connection.transaction(|connection| {
+        let mut inserted = 0;
+        for chunk in records.chunks(RECORDS_PER_INSERT) {
+            let insertable_records: Vec<NewRecord> = chunk.iter().map(|r| r.into()).collect();
+            inserted += diesel::insert_into(records::table)
+                .values(&insertable_records)
+                .execute(connection)?;
+            on_progress(inserted, records.len());
+        }
+        Ok(inserted)
+    })
 }
 
 pub fn select_descriptions(connection: &mut PgConnection) -> Vec<super::models::Description> {
     use super::schema::description_information;
 
-    let results = description_information::table
+    description_information::table
         .select(Description::as_select())
         .load(connection)
-        .expect("Error loading descriptions");
-    return results;
+        .expect("Error loading descriptions")
 }
 
-pub fn insert_description<'a>(
+/// Insert description information, replacing the information of descriptions that already have some
+pub fn insert_description(
     connection: &mut PgConnection,
-    descriptions: &'a [UploadDescription],
+    descriptions: &[UploadDescription],
 ) -> QueryResult<usize> {
-    use super::schema::description_information;
+    use super::schema::description_information::dsl;
+    use diesel::upsert::excluded;
     let insertable_records: Vec<NewDescription> = descriptions.iter().map(|r| r.into()).collect();
-    diesel::insert_into(description_information::table)
+    diesel::insert_into(dsl::description_information)
         .values(&insertable_records)
+        .on_conflict(dsl::description)
+        .do_update()
+        .set((
+            dsl::primary_information.eq(excluded(dsl::primary_information)),
+            dsl::secondary_information.eq(excluded(dsl::secondary_information)),
+            dsl::tertiary_information.eq(excluded(dsl::tertiary_information)),
+            dsl::additional_information.eq(excluded(dsl::additional_information)),
+            dsl::category_id.eq(excluded(dsl::category_id)),
+        ))
         .execute(connection)
 }
+
+// Escape the LIKE wildcards in `term`, so it only matches literally
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// An ILIKE pattern matching `term` anywhere
+fn contains_pattern(term: &str) -> String {
+    format!("%{}%", escape_like(term))
+}
+
+/// Descriptions whose name or information contains `term`, ignoring case
+pub fn search_descriptions(
+    connection: &mut PgConnection,
+    term: &str,
+) -> QueryResult<Vec<Description>> {
+    use super::schema::description_information::dsl;
+    let pattern = contains_pattern(term);
+    dsl::description_information
+        .filter(
+            dsl::description
+                .ilike(&pattern)
+                .or(dsl::primary_information.ilike(&pattern))
+                .or(dsl::secondary_information.ilike(&pattern))
+                .or(dsl::tertiary_information.ilike(&pattern))
+                .or(dsl::additional_information.ilike(&pattern)),
+        )
+        .select(Description::as_select())
+        .order(dsl::description)
+        .load(connection)
+}
+
+/// Every distinct record description without information stored for it, i.e. the tagging queue.
+/// Descriptions skipped in one session stay in the queue until they are tagged.
+pub fn select_untagged_descriptions(connection: &mut PgConnection) -> QueryResult<Vec<String>> {
+    use super::schema::{description_information, records::dsl};
+    use diesel::dsl::{exists, not};
+
+    dsl::records
+        .select(dsl::description)
+        .distinct()
+        .filter(not(exists(description_information::table.filter(
+            description_information::description.eq(dsl::description),
+        ))))
+        .order(dsl::description)
+        .load(connection)
+}
+
+/// What identifies each record dated between `from` and `to`: its account, date, amount and raw
+/// description. Records imported before raw descriptions were kept report their standardized one.
+pub fn select_record_keys(
+    connection: &mut PgConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> QueryResult<Vec<(i32, NaiveDate, f64, String)>> {
+    use super::schema::records::dsl;
+
+    let keys: Vec<(i32, NaiveDate, f64, String, Option<String>)> = dsl::records
+        .filter(dsl::date.between(from, to))
+        .select((
+            dsl::account_id,
+            dsl::date,
+            dsl::amount,
+            dsl::description,
+            dsl::raw_description,
+        ))
+        .load(connection)?;
+    Ok(keys
+        .into_iter()
+        .map(|(account_id, date, amount, description, raw_description)| {
+            (
+                account_id,
+                date,
+                amount,
+                raw_description.unwrap_or(description),
+            )
+        })
+        .collect())
+}
+
+/// Records matching `filter` with their account and description information, newest first
+pub fn select_record_details(
+    connection: &mut PgConnection,
+    filter: &RecordFilter,
+) -> QueryResult<Vec<RecordDetail>> {
+    use super::schema::{accounts, description_information as information, records};
+
+    let mut query = records::table
+        .inner_join(accounts::table)
+        .left_join(information::table.on(information::description.eq(records::description)))
+        .select((
+            records::date,
+            records::amount,
+            accounts::name,
+            records::description,
+            records::raw_description,
+            records::city,
+            records::state,
+            records::country,
+            information::primary_information.nullable(),
+            information::secondary_information.nullable(),
+            information::tertiary_information.nullable(),
+            information::additional_information.nullable(),
+        ))
+        .order((records::date.desc(), records::id.desc()))
+        .into_boxed();
+
+    if let Some(from) = filter.from {
+        query = query.filter(records::date.ge(from));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(records::date.le(to));
+    }
+    if let Some(account) = &filter.account {
+        query = query.filter(accounts::name.ilike(escape_like(account)));
+    }
+    if let Some(term) = &filter.term {
+        let pattern = contains_pattern(term);
+        query = query.filter(
+            records::description
+                .ilike(pattern.clone())
+                .or(records::raw_description.ilike(pattern)),
+        );
+    }
+    if let Some(limit) = filter.limit {
+        query = query.limit(limit);
+    }
+    query.load(connection)
+}
+
+pub fn select_accounts(connection: &mut PgConnection) -> QueryResult<Vec<Account>> {
+    use super::schema::accounts;
+
+    accounts::table
+        .select(Account::as_select())
+        .order(accounts::id)
+        .load(connection)
+}
+
+pub fn insert_account(connection: &mut PgConnection, account: &NewAccount) -> QueryResult<Account> {
+    use super::schema::accounts;
+    diesel::insert_into(accounts::table)
+        .values(account)
+        .returning(Account::as_returning())
+        .get_result(connection)
+}
+
+pub fn select_categories(connection: &mut PgConnection) -> Vec<Category> {
+    use super::schema::categories;
+
+    categories::table
+        .select(Category::as_select())
+        .order(categories::id)
+        .load(connection)
+        .expect("Error loading categories")
+}
+
+pub fn insert_category(
+    connection: &mut PgConnection,
+    category: &NewCategory,
+) -> QueryResult<Category> {
+    use super::schema::categories;
+    diesel::insert_into(categories::table)
+        .values(category)
+        .returning(Category::as_returning())
+        .get_result(connection)
+}
+
+pub fn update_description_category(
+    connection: &mut PgConnection,
+    description: &str,
+    names: [Option<&str>; 3],
+    category_id: Option<i32>,
+) -> QueryResult<usize> {
+    use super::schema::description_information::dsl;
+    let [primary, secondary, tertiary] = names;
+    diesel::update(dsl::description_information.find(description))
+        .set((
+            dsl::primary_information.eq(primary),
+            dsl::secondary_information.eq(secondary),
+            dsl::tertiary_information.eq(tertiary),
+            dsl::category_id.eq(category_id),
+        ))
+        .execute(connection)
+}
+
+pub fn select_record_descriptions(
+    connection: &mut PgConnection,
+) -> QueryResult<Vec<(i64, Option<String>, String)>> {
+    use super::schema::records::dsl;
+    dsl::records
+        .select((dsl::id, dsl::raw_description, dsl::description))
+        .order(dsl::id)
+        .load(connection)
+}
+
+pub fn update_record_descriptions(
+    connection: &mut PgConnection,
+    ids: &[i64],
+    description: &str,
+) -> QueryResult<usize> {
+    use super::schema::records::dsl;
+    // Postgres binds the ids as a single array, so any number of them fits in one statement
+    diesel::update(dsl::records.filter(dsl::id.eq_any(ids)))
+        .set(dsl::description.eq(description))
+        .execute(connection)
+}
+
+pub fn count_records_with_description(
+    connection: &mut PgConnection,
+    description: &str,
+) -> QueryResult<i64> {
+    use super::schema::records::dsl;
+    dsl::records
+        .filter(dsl::description.eq(description))
+        .count()
+        .get_result(connection)
+}
+
+pub fn select_description(
+    connection: &mut PgConnection,
+    description: &str,
+) -> QueryResult<Option<Description>> {
+    use super::schema::description_information::dsl;
+    dsl::description_information
+        .find(description)
+        .select(Description::as_select())
+        .first(connection)
+        .optional()
+}
+
+pub fn delete_description(connection: &mut PgConnection, description: &str) -> QueryResult<usize> {
+    use super::schema::description_information::dsl;
+    diesel::delete(dsl::description_information.find(description)).execute(connection)
+}
+
+/// Every distinct raw description with the description it is stored under and how many records use it.
+/// Records imported before raw descriptions were kept report their standardized description as raw.
+pub fn select_raw_descriptions(
+    connection: &mut PgConnection,
+) -> QueryResult<Vec<(String, String, i64)>> {
+    use super::schema::records::dsl;
+    use diesel::dsl::count_star;
+
+    let rows: Vec<(Option<String>, String, i64)> = dsl::records
+        .group_by((dsl::raw_description, dsl::description))
+        .select((dsl::raw_description, dsl::description, count_star()))
+        .order((dsl::description, dsl::raw_description))
+        .load(connection)?;
+    Ok(rows
+        .into_iter()
+        .map(|(raw_description, description, count)| {
+            (
+                raw_description.unwrap_or_else(|| description.clone()),
+                description,
+                count,
+            )
+        })
+        .collect())
+}
+
+// Description, count, total, average, first and last date
+type SpendTotals = (
+    String,
+    i64,
+    Option<f64>,
+    Option<f64>,
+    Option<NaiveDate>,
+    Option<NaiveDate>,
+);
+
+/// Spend context for every description that has records, keyed by description
+pub fn select_description_spend(
+    connection: &mut PgConnection,
+) -> QueryResult<HashMap<String, DescriptionSpend>> {
+    use super::schema::{accounts, records::dsl};
+    use diesel::dsl::count_star;
+
+    let totals: Vec<SpendTotals> = dsl::records
+        .group_by(dsl::description)
+        .select((
+            dsl::description,
+            count_star(),
+            diesel::dsl::sum(dsl::amount),
+            diesel::dsl::avg(dsl::amount),
+            diesel::dsl::min(dsl::date),
+            diesel::dsl::max(dsl::date),
+        ))
+        .load(connection)?;
+
+    let mut spend: HashMap<String, DescriptionSpend> = HashMap::new();
+    for (description, transactions, total, average, first_date, last_date) in totals {
+        let (Some(first_date), Some(last_date)) = (first_date, last_date) else {
+            continue;
+        };
+        spend.insert(
+            description,
+            DescriptionSpend {
+                transactions,
+                total: total.unwrap_or(0.0),
+                average: average.unwrap_or(0.0),
+                first_date,
+                last_date,
+                accounts: Vec::new(),
+                raw_descriptions: Vec::new(),
+            },
+        );
+    }
+
+    let description_accounts: Vec<(String, String)> = dsl::records
+        .inner_join(accounts::table)
+        .select((dsl::description, accounts::name))
+        .distinct()
+        .order((dsl::description, accounts::name))
+        .load(connection)?;
+    for (description, account) in description_accounts {
+        if let Some(spend) = spend.get_mut(&description) {
+            spend.accounts.push(account);
+        }
+    }
+
+    let raw_descriptions: Vec<(String, Option<String>)> = dsl::records
+        .select((dsl::description, dsl::raw_description))
+        .distinct()
+        .order((dsl::description, dsl::raw_description))
+        .load(connection)?;
+    for (description, raw_description) in raw_descriptions {
+        if let (Some(spend), Some(raw_description)) = (spend.get_mut(&description), raw_description)
+        {
+            spend.raw_descriptions.push(raw_description);
+        }
+    }
+
+    Ok(spend)
+}
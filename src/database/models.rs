@@ -1,9 +1,52 @@
 use crate::{BudgetRecord, UploadDescription};
 
-use super::schema::{description_information, records};
+use super::schema::{accounts, categories, description_information, records};
 use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
 
+#[allow(dead_code)]
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = accounts)]
+pub struct Account {
+    pub id: i32,
+    pub name: String,
+    pub account_type: Option<String>,
+    pub owner: Option<String>,
+    pub last_four: Option<String>,
+    pub credit_limit: Option<f64>,
+    pub closing_day: Option<i16>,
+    pub event_time: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = accounts)]
+pub struct NewAccount<'a> {
+    pub name: &'a str,
+    pub account_type: Option<&'a str>,
+    pub owner: Option<&'a str>,
+    pub last_four: Option<&'a str>,
+    pub credit_limit: Option<f64>,
+    pub closing_day: Option<i16>,
+}
+
+#[allow(dead_code)]
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = categories)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub event_time: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = categories)]
+pub struct NewCategory<'a> {
+    pub name: &'a str,
+    pub parent_id: Option<i32>,
+}
+
+#[allow(dead_code)]
 #[derive(Queryable, Selectable, Debug)]
 #[diesel(table_name = records)]
 #[diesel(primary_key())]
@@ -11,18 +54,29 @@ pub struct Record {
     pub id: i64,
     pub amount: f64,
     pub date: NaiveDate,
-    pub card: String,
+    pub account_id: i32,
     pub description: String,
     pub event_time: DateTime<Utc>,
+    pub raw_description: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
 }
 
+// How many values each NewRecord binds in an insert. Keep in step with its fields.
+pub const NEW_RECORD_COLUMNS: usize = 8;
+
 #[derive(Insertable, Debug)]
 #[diesel(table_name = records)]
 pub struct NewRecord<'a> {
     pub amount: f64,
     pub date: NaiveDate,
-    pub card: &'a str,
+    pub account_id: i32,
     pub description: &'a str,
+    pub raw_description: &'a str,
+    pub city: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub country: Option<&'a str>,
 }
 
 impl<'a> From<&'a BudgetRecord> for NewRecord<'a> {
@@ -30,8 +84,12 @@ impl<'a> From<&'a BudgetRecord> for NewRecord<'a> {
         NewRecord {
             amount: record.amount,
             date: record.date,
-            card: &record.card,
+            account_id: record.account_id,
             description: &record.description,
+            raw_description: &record.raw_description,
+            city: record.location.city.as_deref(),
+            state: record.location.state.as_deref(),
+            country: record.location.country.as_deref(),
         }
     }
 }
@@ -45,6 +103,7 @@ pub struct Description {
     pub tertiary_information: Option<String>,
     pub additional_information: Option<String>,
     pub event_time: DateTime<Utc>,
+    pub category_id: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -55,6 +114,7 @@ pub struct NewDescription<'a> {
     pub secondary_information: &'a str,
     pub tertiary_information: &'a str,
     pub additional_information: &'a str,
+    pub category_id: Option<i32>,
 }
 
 impl<'a> From<&'a UploadDescription> for NewDescription<'a> {
@@ -65,6 +125,72 @@ impl<'a> From<&'a UploadDescription> for NewDescription<'a> {
             secondary_information: &description.secondary_information,
             tertiary_information: &description.tertiary_information,
             additional_information: &description.additional_information,
+            category_id: description.category_id,
         }
     }
 }
+
+// How much a description has been spent on, gathered from its records
+#[derive(Debug, Clone)]
+pub struct DescriptionSpend {
+    pub transactions: i64,
+    pub total: f64,
+    pub average: f64,
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    pub accounts: Vec<String>,
+    pub raw_descriptions: Vec<String>,
+}
+
+// A record with its account name and description information, as read for reports and exports
+#[derive(Queryable, Debug)]
+pub struct RecordDetail {
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub account: String,
+    pub description: String,
+    pub raw_description: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub primary_information: Option<String>,
+    pub secondary_information: Option<String>,
+    pub tertiary_information: Option<String>,
+    pub additional_information: Option<String>,
+}
+
+// Which records to read. Every condition that is set must match.
+#[derive(Debug, Default)]
+pub struct RecordFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub account: Option<String>,
+    // Matched against the standardized and raw description, ignoring case
+    pub term: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::{debug_query, pg::Pg};
+
+    #[test]
+    fn new_record_columns_matches_its_binds() {
+        let record = NewRecord {
+            amount: -4.5,
+            date: NaiveDate::from_ymd_opt(2025, 6, 14).unwrap(),
+            account_id: 1,
+            description: "blue bottle",
+            raw_description: "SQ *BLUE BOTTLE",
+            // Every column is set, a None is written as DEFAULT without a bind
+            city: Some("Denver"),
+            state: Some("CO"),
+            country: Some("USA"),
+        };
+        let insert = diesel::insert_into(records::table).values(&record);
+        let sql = debug_query::<Pg, _>(&insert).to_string();
+        assert!(sql.contains(&format!("${NEW_RECORD_COLUMNS}")));
+        assert!(!sql.contains(&format!("${}", NEW_RECORD_COLUMNS + 1)));
+    }
+}
@@ -1,11 +1,37 @@
+diesel::table! {
+    budget.accounts (id) {
+        id -> Integer,
+        name -> Text,
+        account_type -> Nullable<Text>,
+        owner -> Nullable<Text>,
+        last_four -> Nullable<Text>,
+        credit_limit -> Nullable<Double>,
+        closing_day -> Nullable<SmallInt>,
+        event_time -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    budget.categories (id) {
+        id -> Integer,
+        name -> Text,
+        parent_id -> Nullable<Integer>,
+        event_time -> Timestamptz,
+    }
+}
+
 diesel::table! {
     budget.records (id){
         id -> BigInt,
         amount -> Double,
         date -> Date,
-        card -> Text,
+        account_id -> Integer,
         description -> Text,
         event_time -> Timestamptz,
+        raw_description -> Nullable<Text>,
+        city -> Nullable<Text>,
+        state -> Nullable<Text>,
+        country -> Nullable<Text>,
     }
 }
 
@@ -17,5 +43,16 @@ diesel::table! {
         tertiary_information -> Nullable<Text>,
         additional_information -> Nullable<Text>,
         event_time -> Timestamptz,
+        category_id -> Nullable<Integer>,
     }
 }
+
+diesel::joinable!(records -> accounts (account_id));
+diesel::joinable!(description_information -> categories (category_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    accounts,
+    categories,
+    records,
+    description_information
+);
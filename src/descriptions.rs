@@ -0,0 +1,113 @@
+use diesel::PgConnection;
+
+use crate::{
+    categories::{self, CategoryTree},
+    classifier::CategoryClassifier,
+    confirm,
+    database::{db, models::Description},
+    tagging, UploadDescription,
+};
+
+fn print_description(description: &Description, tree: &CategoryTree) {
+    println!("Description: {}", description.description);
+    let fields = [
+        ("Primary", &description.primary_information),
+        ("Secondary", &description.secondary_information),
+        ("Tertiary", &description.tertiary_information),
+        ("Additional", &description.additional_information),
+    ];
+    for (name, value) in fields {
+        println!("  {name}: {}", value.as_deref().unwrap_or(""));
+    }
+    if let Some(category_id) = description.category_id {
+        println!("  Category: {}", tree.path(category_id).join(" > "));
+    }
+    println!("  Last changed: {}", description.event_time);
+}
+
+// The description with exactly this name, or otherwise every description matching it as a search term
+fn find_descriptions(connection: &mut PgConnection, term: &str) -> Vec<Description> {
+    let result = match db::select_description(connection, term) {
+        Ok(Some(description)) => Ok(vec![description]),
+        Ok(None) => db::search_descriptions(connection, term),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(descriptions) => descriptions,
+        Err(e) => panic!("There was an error searching the descriptions. Error: {e}"),
+    }
+}
+
+/// Edit the information of every description matching `term` in the tagging screen
+pub fn edit(connection: &mut PgConnection, term: &str) {
+    let descriptions = find_descriptions(connection, term);
+    if descriptions.is_empty() {
+        println!("No descriptions match '{term}'.");
+        return;
+    }
+
+    let all_descriptions = db::select_descriptions(connection);
+    let mut classifier = CategoryClassifier::train(&all_descriptions);
+    let tagged: Vec<UploadDescription> = all_descriptions
+        .iter()
+        .map(UploadDescription::from)
+        .collect();
+    let editing: Vec<UploadDescription> =
+        descriptions.iter().map(UploadDescription::from).collect();
+
+    let spend = match db::select_description_spend(connection) {
+        Ok(spend) => spend,
+        Err(e) => panic!("There was an error loading the spend per description. Error: {e}"),
+    };
+
+    let title = format!("Editing descriptions matching '{term}'");
+    let mut changed =
+        match tagging::tag_descriptions(&title, editing, &tagged, &mut classifier, &spend) {
+            Ok(changed) => changed,
+            Err(e) => panic!("There was an error while editing the descriptions. Error: {e}"),
+        };
+    if changed.is_empty() {
+        println!("No descriptions were changed.");
+        return;
+    }
+
+    categories::link_descriptions(connection, &mut changed);
+    match db::insert_description(connection, &changed) {
+        Ok(count) => println!("Updated {count} description(s)."),
+        Err(e) => panic!("There was an error updating the description information. Error: {e}"),
+    }
+}
+
+/// Delete the information of a description after confirming it
+pub fn delete(connection: &mut PgConnection, name: &str) {
+    let description = match db::select_description(connection, name) {
+        Ok(Some(description)) => description,
+        Ok(None) => {
+            println!("There is no information stored for description '{name}'.");
+            return;
+        }
+        Err(e) => panic!("There was an error loading description '{name}'. Error: {e}"),
+    };
+
+    print_description(&description, &CategoryTree::load(connection));
+    if !confirm("Delete this description's information?") {
+        println!("Nothing was deleted.");
+        return;
+    }
+    match db::delete_description(connection, name) {
+        Ok(_) => println!("Deleted the information for '{name}'."),
+        Err(e) => panic!("There was an error deleting description '{name}'. Error: {e}"),
+    }
+}
+
+/// Print the description named `term`, or otherwise every description matching it
+pub fn show(connection: &mut PgConnection, term: &str) {
+    let descriptions = find_descriptions(connection, term);
+    if descriptions.is_empty() {
+        println!("No descriptions match '{term}'.");
+    }
+    let tree = CategoryTree::load(connection);
+    for description in &descriptions {
+        print_description(description, &tree);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use unicode_width::UnicodeWidthStr;
+
+// Values kept per field, oldest first
+const HISTORY_LIMIT: usize = 100;
+
+static HISTORY: LazyLock<Mutex<HashMap<String, Vec<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The values entered for `field` so far in this run, oldest first
+pub fn history(field: &str) -> Vec<String> {
+    let history = HISTORY.lock().unwrap();
+    history.get(field).cloned().unwrap_or_default()
+}
+
+/// Add a value to the history of `field`, moving it to the newest spot if it was already there
+pub fn remember(field: &str, value: &str) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    let mut history = HISTORY.lock().unwrap();
+    let values = history.entry(field.to_string()).or_default();
+    values.retain(|existing| existing != value);
+    values.push(value.to_string());
+    if values.len() > HISTORY_LIMIT {
+        values.remove(0);
+    }
+}
+
+/// A single line of text being edited, with a cursor that can move anywhere in it
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    // Byte offset of the cursor, always on a character boundary
+    cursor: usize,
+    // How far back in the history the shown value is, and what was typed before browsing it
+    browsing: Option<usize>,
+    draft: String,
+}
+
+impl LineEditor {
+    pub fn new(text: &str) -> Self {
+        LineEditor {
+            text: text.to_string(),
+            cursor: text.len(),
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = text.len();
+        self.browsing = None;
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.text.len()
+    }
+
+    /// Columns taken up by the text before the cursor, which differs from its length for wide
+    /// and combining characters
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.cursor].width()
+    }
+
+    /// Insert typed or pasted text at the cursor. Line breaks and tabs become spaces and other
+    /// control characters are dropped, so a paste can't submit or break the line.
+    pub fn insert(&mut self, text: &str) {
+        let clean: String = text
+            .replace("\r\n", "\n")
+            .chars()
+            .filter_map(|c| match c {
+                '\n' | '\r' | '\t' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        self.text.insert_str(self.cursor, &clean);
+        self.cursor += clean.len();
+        self.browsing = None;
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    // Start of the word before the cursor, skipping any spaces right before it
+    fn word_start(&self) -> usize {
+        let before = self.text[..self.cursor].trim_end();
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(index, c)| index + c.len_utf8())
+    }
+
+    // End of the word after the cursor, skipping any spaces right after it
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let start = after.len() - after.trim_start().len();
+        let rest = &after[start..];
+        self.cursor + start + rest.find(char::is_whitespace).unwrap_or(rest.len())
+    }
+
+    fn delete(&mut self, start: usize, end: usize) {
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+        self.browsing = None;
+    }
+
+    /// Apply an editing key. Returns whether the key was an editing key at all, so callers can
+    /// handle the rest themselves.
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let control = modifiers.contains(KeyModifiers::CONTROL);
+        let alt = modifiers.contains(KeyModifiers::ALT);
+        match code {
+            KeyCode::Left if control || alt => self.cursor = self.word_start(),
+            KeyCode::Right if control || alt => self.cursor = self.word_end(),
+            KeyCode::Char('b') if alt => self.cursor = self.word_start(),
+            KeyCode::Char('f') if alt => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.previous_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if control => self.cursor = self.text.len(),
+            KeyCode::Backspace if control || alt => self.delete(self.word_start(), self.cursor),
+            KeyCode::Char('w') if control => self.delete(self.word_start(), self.cursor),
+            KeyCode::Char('u') if control => self.delete(0, self.cursor),
+            KeyCode::Char('k') if control => self.delete(self.cursor, self.text.len()),
+            KeyCode::Backspace => self.delete(self.previous_boundary(), self.cursor),
+            KeyCode::Delete => self.delete(self.cursor, self.next_boundary()),
+            KeyCode::Char(c) if !control && !alt => self.insert(c.encode_utf8(&mut [0; 4])),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Show the previous value from `history`, newest first, keeping what was typed to come back to
+    pub fn history_previous(&mut self, history: &[String]) {
+        let back = self.browsing.map_or(0, |back| back + 1);
+        if back >= history.len() {
+            return;
+        }
+        if self.browsing.is_none() {
+            self.draft = self.text.clone();
+        }
+        self.set_text(&history[history.len() - 1 - back]);
+        self.browsing = Some(back);
+    }
+
+    /// Show the next value from `history`, or what was typed before browsing it
+    pub fn history_next(&mut self, history: &[String]) {
+        match self.browsing {
+            None => {}
+            Some(0) => {
+                let draft = std::mem::take(&mut self.draft);
+                self.set_text(&draft);
+            }
+            Some(back) => {
+                self.set_text(&history[history.len() - back]);
+                self.browsing = Some(back - 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) {
+        assert!(editor.handle_key(code, modifiers));
+    }
+
+    fn type_text(editor: &mut LineEditor, text: &str) {
+        for c in text.chars() {
+            press(editor, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    #[test]
+    fn typing_inserts_at_the_cursor() {
+        let mut editor = LineEditor::new("food dining");
+        for _ in 0.."dining".len() {
+            press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        }
+        type_text(&mut editor, "& ");
+        assert_eq!(editor.text(), "food & dining");
+        assert!(!editor.at_end());
+    }
+
+    #[test]
+    fn home_and_end() {
+        let mut editor = LineEditor::new("travel");
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        type_text(&mut editor, "air ");
+        press(&mut editor, KeyCode::End, KeyModifiers::NONE);
+        type_text(&mut editor, "s");
+        assert_eq!(editor.text(), "air travels");
+        assert!(editor.at_end());
+    }
+
+    #[test]
+    fn word_movement_skips_spaces() {
+        let mut editor = LineEditor::new("one two  three");
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        type_text(&mut editor, "|");
+        assert_eq!(editor.text(), "one two  |three");
+
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('f'), KeyModifiers::ALT);
+        type_text(&mut editor, "|");
+        assert_eq!(editor.text(), "one| two  |three");
+    }
+
+    #[test]
+    fn word_and_line_deletion() {
+        let mut editor = LineEditor::new("groceries and household");
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), "groceries and ");
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::ALT);
+        assert_eq!(editor.text(), "groceries ");
+
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), "");
+
+        let mut editor = LineEditor::new("groceries");
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(editor.text(), "s");
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_whole_characters() {
+        let mut editor = LineEditor::new("café");
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(editor.text(), "caf");
+
+        let mut editor = LineEditor::new("éclair");
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(editor.text(), "clair");
+    }
+
+    #[test]
+    fn cursor_width_counts_columns() {
+        let mut editor = LineEditor::new("寿司 bar");
+        for _ in 0.."bar".len() + 1 {
+            press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        }
+        assert_eq!(editor.cursor_width(), 4);
+    }
+
+    #[test]
+    fn other_keys_are_left_to_the_caller() {
+        let mut editor = LineEditor::new("rent");
+        assert!(!editor.handle_key(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(!editor.handle_key(KeyCode::Tab, KeyModifiers::NONE));
+        assert!(!editor.handle_key(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(editor.text(), "rent");
+    }
+
+    #[test]
+    fn paste_flattens_line_breaks_and_drops_controls() {
+        let mut editor = LineEditor::default();
+        editor.insert("coffee\r\nshops\tand\u{7}bakeries\n");
+        assert_eq!(editor.text(), "coffee shops andbakeries ");
+    }
+
+    #[test]
+    fn history_browsing_returns_to_the_draft() {
+        let history = vec!["rent".to_string(), "groceries".to_string()];
+        let mut editor = LineEditor::new("gro");
+        editor.history_previous(&history);
+        assert_eq!(editor.text(), "groceries");
+        editor.history_previous(&history);
+        assert_eq!(editor.text(), "rent");
+        editor.history_previous(&history);
+        assert_eq!(editor.text(), "rent");
+        editor.history_next(&history);
+        assert_eq!(editor.text(), "groceries");
+        editor.history_next(&history);
+        assert_eq!(editor.text(), "gro");
+    }
+}
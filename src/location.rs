@@ -0,0 +1,311 @@
+use std::fmt;
+
+use crate::standardize::noise_free_words;
+
+const US_STATES: &[&str] = &[
+    "al", "ak", "az", "ar", "ca", "co", "ct", "de", "dc", "fl", "ga", "hi", "id", "il", "in", "ia",
+    "ks", "ky", "la", "me", "md", "ma", "mi", "mn", "ms", "mo", "mt", "ne", "nv", "nh", "nj", "nm",
+    "ny", "nc", "nd", "oh", "ok", "or", "pa", "ri", "sc", "sd", "tn", "tx", "ut", "vt", "va", "wa",
+    "wv", "wi", "wy",
+];
+
+// ISO 3166 alpha-3 codes that foreign card transactions end in. Codes that are also English words,
+// such as "can", "pan" or "are", are left out since merchant names end in them far more often.
+const COUNTRIES: &[&str] = &[
+    "usa", "mex", "gbr", "irl", "fra", "deu", "ita", "esp", "prt", "nld", "bel", "che", "aut",
+    "dnk", "swe", "nor", "fin", "isl", "grc", "hrv", "cze", "pol", "tur", "jpn", "kor", "chn",
+    "hkg", "sgp", "tha", "vnm", "phl", "idn", "aus", "nzl", "bra", "arg", "chl", "cri", "bhs",
+    "isr", "zaf", "egy",
+];
+
+// First words of multi word city names, so "fort collins co" keeps the whole city
+const CITY_PREFIXES: &[&str] = &[
+    "fort", "ft", "new", "san", "santa", "los", "las", "st", "saint", "el", "north", "south",
+    "east", "west", "lake", "salt", "palm", "grand", "glen", "castle", "colorado", "little",
+];
+
+// Cities trusted to be a location even without a store number before them. Names that are also
+// common merchant words, such as "golden" or "parker", are left out.
+const KNOWN_CITIES: &[&str] = &[
+    "denver",
+    "boulder",
+    "aurora",
+    "lakewood",
+    "littleton",
+    "englewood",
+    "arvada",
+    "westminster",
+    "thornton",
+    "broomfield",
+    "longmont",
+    "loveland",
+    "fort collins",
+    "colorado springs",
+    "castle rock",
+    "highlands ranch",
+    "pueblo",
+    "greeley",
+    "centennial",
+    "breckenridge",
+    "salt lake city",
+    "seattle",
+    "portland",
+    "chicago",
+    "new york",
+    "los angeles",
+    "san francisco",
+    "san diego",
+    "austin",
+    "dallas",
+    "houston",
+    "phoenix",
+    "atlanta",
+    "boston",
+    "miami",
+    "orlando",
+    "las vegas",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<&str> = [&self.city, &self.state, &self.country]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+fn title_case(words: &[&str]) -> String {
+    words
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn is_city_word(word: &str) -> bool {
+    word.len() >= 3 && word.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+// Where the city before a state starts, if the words before the state read as one. `words` ends
+// right before the state. The merchant always keeps at least one word.
+fn find_city(words: &[&str], after_noise: &[bool], has_country: bool) -> Option<usize> {
+    let end = words.len();
+
+    // A known city needs a second merchant word, or a removed store number or country to back it
+    let known = (1..=3).rev().filter(|len| *len < end).find_map(|len| {
+        let start = end - len;
+        let city = words[start..].join(" ");
+        KNOWN_CITIES.contains(&city.as_str()).then_some(start)
+    });
+    if let Some(start) = known {
+        if start >= 2 || after_noise[start] || has_country {
+            return Some(start);
+        }
+    }
+
+    if end < 2 || !is_city_word(words[end - 1]) {
+        return None;
+    }
+    let mut start = end - 1;
+    while start > 1 && end - start < 3 && CITY_PREFIXES.contains(&words[start - 1]) {
+        start -= 1;
+    }
+    // Otherwise the city has to follow a removed store number or id, and words before it are merchant
+    match (start..end).rev().find(|index| after_noise[*index]) {
+        Some(index) => Some(index),
+        None if has_country => Some(start),
+        None => None,
+    }
+}
+
+/// Split noise free, lowercase description words into the merchant and a trailing location.
+/// `after_noise` tells for each word whether noise was removed right before it.
+/// Returns how many leading words belong to the merchant, which is always at least one.
+pub fn split_location(words: &[&str], after_noise: &[bool]) -> (usize, Option<Location>) {
+    let mut end = words.len();
+    let mut location = Location::default();
+
+    // A country follows a city, or a US state, with at least one merchant word before that
+    let has_country = end >= 3
+        && COUNTRIES.contains(&words[end - 1])
+        && (is_city_word(words[end - 2]) || US_STATES.contains(&words[end - 2]));
+    if has_country {
+        location.country = Some(words[end - 1].to_uppercase());
+        end -= 1;
+    }
+
+    // Only US transactions name a state
+    let city_start = (end >= 3
+        && US_STATES.contains(&words[end - 1])
+        && matches!(location.country.as_deref(), None | Some("USA")))
+    .then(|| find_city(&words[..end - 1], after_noise, has_country))
+    .flatten();
+    if let Some(city_start) = city_start {
+        location.state = Some(words[end - 1].to_uppercase());
+        location.country = Some("USA".to_string());
+        end -= 1;
+        location.city = Some(title_case(&words[city_start..end]));
+        end = city_start;
+    } else if location.country.is_some() && end >= 2 && is_city_word(words[end - 1]) {
+        // Foreign transactions read "<merchant> <city> <country>"
+        location.city = Some(title_case(&words[end - 1..end]));
+        end -= 1;
+    }
+
+    if location == Location::default() {
+        return (words.len(), None);
+    }
+    (end, Some(location))
+}
+
+/// The city, state and country a card transaction was made in, when the bank included them
+pub fn extract_location(description: &str) -> Option<Location> {
+    let noise_free = noise_free_words(description);
+    let words: Vec<&str> = noise_free.iter().map(|(word, _)| word.as_str()).collect();
+    let after_noise: Vec<bool> = noise_free
+        .iter()
+        .map(|(_, after_noise)| *after_noise)
+        .collect();
+    split_location(&words, &after_noise).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(description: &str) -> (usize, Option<Location>) {
+        let noise_free = noise_free_words(description);
+        let words: Vec<&str> = noise_free.iter().map(|(word, _)| word.as_str()).collect();
+        let after_noise: Vec<bool> = noise_free
+            .iter()
+            .map(|(_, after_noise)| *after_noise)
+            .collect();
+        split_location(&words, &after_noise)
+    }
+
+    fn location(city: &str, state: Option<&str>, country: &str) -> Option<Location> {
+        Some(Location {
+            city: Some(city.to_string()),
+            state: state.map(String::from),
+            country: Some(country.to_string()),
+        })
+    }
+
+    #[test]
+    fn us_city_and_state() {
+        assert_eq!(
+            split("king soopers denver co"),
+            (2, location("Denver", Some("CO"), "USA"))
+        );
+    }
+
+    #[test]
+    fn multi_word_city() {
+        assert_eq!(
+            split("blue bottle fort collins co"),
+            (2, location("Fort Collins", Some("CO"), "USA"))
+        );
+    }
+
+    #[test]
+    fn state_with_trailing_country() {
+        assert_eq!(
+            split("starbucks denver co usa"),
+            (1, location("Denver", Some("CO"), "USA"))
+        );
+    }
+
+    #[test]
+    fn foreign_city_and_country() {
+        assert_eq!(
+            split("cafe de flore paris fra"),
+            (3, location("Paris", None, "FRA"))
+        );
+    }
+
+    #[test]
+    fn merchant_needs_a_word_besides_the_location() {
+        assert_eq!(split("denver co"), (2, None));
+        assert_eq!(split("paris fra"), (2, None));
+    }
+
+    #[test]
+    fn words_that_are_country_codes_stay_in_the_merchant() {
+        assert_eq!(split("peter pan"), (2, None));
+        assert_eq!(split("peter pan bakery pan"), (4, None));
+        assert_eq!(split("yes we can"), (3, None));
+        assert_eq!(split("pots and pans per"), (4, None));
+    }
+
+    #[test]
+    fn country_needs_a_city_before_it() {
+        assert_eq!(split("hotel 12 fra"), (3, None));
+    }
+
+    #[test]
+    fn no_location() {
+        assert_eq!(split("netflix"), (1, None));
+        assert_eq!(split("whole foods market"), (3, None));
+    }
+
+    #[test]
+    fn extracts_from_raw_descriptions() {
+        assert_eq!(
+            extract_location("SQ *BLUE BOTTLE 0042 DENVER CO"),
+            location("Denver", Some("CO"), "USA")
+        );
+    }
+
+    #[test]
+    fn merchants_ending_in_co_have_no_location() {
+        assert_eq!(split("ace hardware co"), (3, None));
+        assert_eq!(split("xcel energy co"), (3, None));
+        assert_eq!(split("rocky mountain chocolate co"), (4, None));
+        assert_eq!(extract_location("ACE HARDWARE CO"), None);
+    }
+
+    #[test]
+    fn city_after_a_store_number() {
+        assert_eq!(
+            split("ace hardware #0412 golden co"),
+            (2, location("Golden", Some("CO"), "USA"))
+        );
+        assert_eq!(
+            split("chipotle 1234 denver co"),
+            (1, location("Denver", Some("CO"), "USA"))
+        );
+    }
+
+    #[test]
+    fn single_word_merchant_needs_a_store_number() {
+        assert_eq!(split("starbucks denver co"), (3, None));
+    }
+
+    #[test]
+    fn three_word_city() {
+        assert_eq!(
+            split("king soopers #0123 salt lake city ut"),
+            (2, location("Salt Lake City", Some("UT"), "USA"))
+        );
+        assert_eq!(
+            split("king soopers salt lake city ut"),
+            (2, location("Salt Lake City", Some("UT"), "USA"))
+        );
+    }
+}
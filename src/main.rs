@@ -1,46 +1,143 @@
 use chrono::NaiveDate;
 use core::fmt;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashSet},
     env,
     error::Error,
     fs,
     fs::File,
-    io::{self, ErrorKind, Write},
-    path::PathBuf,
-    sync::LazyLock,
+    io::{self, ErrorKind, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::Duration,
 };
+mod accounts;
+mod bulk;
+mod categories;
+mod classifier;
+mod cli;
+mod completion;
 mod database;
+mod descriptions;
+mod line_editor;
+mod location;
+mod preview;
+mod report;
+mod restandardize;
+mod rule_bench;
+mod standardize;
+mod suggestions;
+mod tagging;
+mod watch;
+use accounts::AccountResolver;
+use clap::Parser;
+use classifier::CategoryClassifier;
+use cli::{Cli, Command, DescriptionsCommand, Format};
+use completion::Completer;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    cursor::MoveToColumn,
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers,
+    },
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{Clear, ClearType},
 };
-use database::db;
+use database::{db, models::RecordFilter};
+use diesel::PgConnection;
+use line_editor::LineEditor;
+use location::{extract_location, Location};
+use standardize::{StandardizationRule, Standardizer};
+
+const STANDARDIZATION_RULES_PATH: &str = "config/standardization_rules.json";
+// How many similar tagged descriptions to offer while tagging
+const MAX_SUGGESTIONS: usize = 3;
 
 #[derive(Debug)]
 struct BudgetRecord {
     amount: f64,
     date: NaiveDate,
-    card: String,
+    account_id: i32,
     description: String,
+    raw_description: String,
+    location: Location,
 }
 impl fmt::Display for BudgetRecord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Amount: {0} | Date: {1} | Card: {2} | Description: {3}",
-            self.amount, self.date, self.card, self.description
+            "Amount: {0} | Date: {1} | Account: {2} | Description: {3}",
+            self.amount, self.date, self.account_id, self.description
         )
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 struct UploadDescription {
     description: String,
     primary_information: String,
     secondary_information: String,
     tertiary_information: String,
     additional_information: String,
+    category_id: Option<i32>,
+}
+
+impl From<&database::models::Description> for UploadDescription {
+    fn from(description: &database::models::Description) -> Self {
+        UploadDescription {
+            description: description.description.clone(),
+            primary_information: description.primary_information.clone().unwrap_or_default(),
+            secondary_information: description
+                .secondary_information
+                .clone()
+                .unwrap_or_default(),
+            tertiary_information: description.tertiary_information.clone().unwrap_or_default(),
+            additional_information: description
+                .additional_information
+                .clone()
+                .unwrap_or_default(),
+            category_id: description.category_id,
+        }
+    }
+}
+
+// Why a row of a budget file did not become a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkipReason {
+    CardPayment,
+    // The user chose to skip the records of an unknown account
+    SkippedAccount,
+    // An unknown account that couldn't be registered because nobody can be asked
+    UnregisteredAccount,
+    InvalidAmount,
+    InvalidDate,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            SkipReason::CardPayment => "credit card payment",
+            SkipReason::SkippedAccount => "the account was skipped",
+            SkipReason::UnregisteredAccount => "no registered account matches it",
+            SkipReason::InvalidAmount => "the amount could not be read",
+            SkipReason::InvalidDate => "the date could not be read",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+#[derive(Debug)]
+struct SkippedRow {
+    line: u64,
+    row: String,
+    reason: SkipReason,
+}
+
+// The records read from a budget file, and the rows that were left out
+struct BudgetFile {
+    origin: String,
+    records: Vec<BudgetRecord>,
+    skipped: Vec<SkippedRow>,
 }
 
 #[derive(Debug)]
@@ -56,203 +153,289 @@ impl fmt::Display for InvalidHeader {
 
 impl Error for InvalidHeader {}
 
-fn standardize_description(description: &str) -> String {
-    let raw_description = description.to_lowercase();
-    // Hard coded mapping of purchases that contain a UUID in them that I want to standardize
-    static DESCRIPTION_MAP: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
-        let mut map = HashMap::new();
-
-        // Online Stores
-        map.insert("amazon", "amazon");
-        map.insert("amzn", "amazon");
-        map.insert("prime video", "tv");
-        map.insert("amc", "amc");
-        map.insert("petsmart", "petsmart");
-
-        // In Person Stores
-        map.insert("target", "target");
-        map.insert("the home depot", "home depot");
-        map.insert("rei", "rei");
-        map.insert("barnes & noble", "barnes & noble");
-        map.insert("autozone", "autozone");
-        map.insert("crate & barrel", "crate & barrel");
-        map.insert("vca animal hosp", "vca veterinarian");
-        map.insert("laz parking", "laz parking");
-        map.insert("spothero", "spothero");
-        map.insert("walgreens", "walgreens");
-        map.insert("831 bowlero", "bowlero");
-
-        // Airlines & Travel
-        map.insert("united", "united airlines");
-        map.insert("delta", "delta airlines");
-        map.insert("hilton", "hilton");
-        map.insert("airbnb", "airbnb");
-
-        // Restaurants
-        map.insert("ihop", "ihop");
-        map.insert("bonefish", "bonefish");
-        map.insert("chick-fil-a", "chick-fil-a");
-        map.insert("chipotle", "chipotle");
-        map.insert("mad greens", "mad greens");
-        map.insert("domino's", "dominos");
-        map.insert("dunkin", "dunkin donuts");
-        map.insert("panda express", "panda express");
-        map.insert("noodles & co", "noodles & co");
-        map.insert("olive garden", "olive garden");
-        map.insert("oracl*waffle house", "waffle house");
-        map.insert("bop & gogi", "bop & gogi");
-        map.insert("paypal *domino's", "dominos");
-
-        // Gas
-        map.insert("safeway fuel", "safeway fuel");
-        map.insert("king soopers fuel", "king soopers fuel");
-        map.insert("conoco", "conoco");
-        map.insert("phillips 66", "phillips 66");
-        map.insert("stop 4 gas", "stop 4 gas");
-        map.insert("circle k", "circle k");
-        map.insert("shell", "shell");
-        map.insert("7-eleven", "7-eleven");
-        map.insert("qt", "quicktrip");
-        map.insert("chevron", "chevron");
-        map.insert("kum&go", "kum&go");
-
-        // Groceries
-        map.insert("trader joe s", "trader joe's");
-        map.insert("publix", "publix");
-        map.insert("safeway #", "safeway");
-        map.insert("king soopers #", "king soopers");
-
-        map
-    });
-
-    for (prefix, standardized) in DESCRIPTION_MAP.iter() {
-        if raw_description.starts_with(prefix) {
-            return String::from(*standardized);
-        }
-    }
+// Redraw the line being typed, with the rest of its completion dimmed after it
+fn redraw_input(editor: &LineEditor, completer: Option<&Completer>) {
+    let hint = match completer {
+        Some(completer) if editor.at_end() => completer.hint(editor.text()).unwrap_or_default(),
+        _ => String::new(),
+    };
+    let mut out = io::stdout();
+    queue!(
+        out,
+        MoveToColumn(0),
+        Clear(ClearType::UntilNewLine),
+        Print(editor.text()),
+        SetAttribute(Attribute::Dim),
+        Print(&hint),
+        SetAttribute(Attribute::Reset),
+        MoveToColumn(editor.cursor_width() as u16)
+    )
+    .unwrap();
+    out.flush().unwrap();
+}
 
-    raw_description
+/// Read a line of input in raw mode. `field` names what is being entered, so Up and Down browse
+/// the values entered for it earlier in the run.
+fn description_input_parser(field: &str, completer: Option<&Completer>) -> Option<String> {
+    execute!(io::stdout(), EnableBracketedPaste).unwrap();
+    let input = read_input(field, completer);
+    execute!(io::stdout(), DisableBracketedPaste).unwrap();
+    input
 }
 
-fn description_input_parser() -> Option<String> {
-    let mut input = String::new();
+fn read_input(field: &str, completer: Option<&Completer>) -> Option<String> {
+    let mut editor = LineEditor::default();
+    let history = line_editor::history(field);
+    // Whether the user was already warned that the input is close to an existing value
+    let mut warned = false;
 
     loop {
-        if let Event::Key(KeyEvent {
-            code,
-            modifiers,
-            kind: _,
-            state: _,
-        }) = event::read().unwrap()
-        {
-            match (code, modifiers) {
-                (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
-                    println!("\r");
-                    return None;
-                }
-                (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
-                    println!("\r");
-                    return Some("CRTL+A_ABORT".into());
-                }
-                (KeyCode::Enter, _) => {
+        let (code, modifiers) = match event::read().unwrap() {
+            Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                state: _,
+            }) => (code, modifiers),
+            Event::Paste(text) => {
+                editor.insert(&text);
+                warned = false;
+                redraw_input(&editor, completer);
+                continue;
+            }
+            _ => continue,
+        };
+
+        match (code, modifiers) {
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                println!("\r");
+                return None;
+            }
+            (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+                println!("\r");
+                return Some("CRTL+A_ABORT".into());
+            }
+            (KeyCode::Enter, _) => {
+                let input = editor.text().to_string();
+                let close = completer.and_then(|completer| completer.close_to(&input));
+                if let (Some(existing), false) = (close, warned) {
                     println!("\r");
-                    return Some(input);
+                    println!("'{}' is close to the existing '{existing}'. Press Enter again to keep it.\r", input.trim());
+                    warned = true;
+                    redraw_input(&editor, completer);
+                    continue;
                 }
-                (KeyCode::Char(c), _) => {
-                    print!("{c}");
-                    io::stdout().flush().unwrap();
-                    input.push(c);
+                redraw_input(&editor, None);
+                println!("\r");
+                line_editor::remember(field, &input);
+                return Some(input);
+            }
+            (KeyCode::Tab, _) => {
+                if let Some(completion) =
+                    completer.and_then(|completer| completer.complete(editor.text()))
+                {
+                    editor.set_text(completion);
+                    warned = false;
                 }
-                (KeyCode::Backspace, _) => {
-                    if input.pop().is_some() {
-                        print!("\x08 \x08");
-                        io::stdout().flush().unwrap();
-                    }
+            }
+            (KeyCode::Up, _) => editor.history_previous(&history),
+            (KeyCode::Down, _) => editor.history_next(&history),
+            (code, modifiers) => {
+                if !editor.handle_key(code, modifiers) {
+                    continue;
                 }
-                _ => {}
+                warned = false;
             }
         }
+        redraw_input(&editor, completer);
+    }
+}
+
+// Ask a yes/no question on the regular (cooked) terminal
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/N] ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
     }
 }
 
-fn parse_record(record: csv::StringRecord, origin: &str) -> Option<BudgetRecord> {
+// Banks write amounts over a thousand with separators, like "1,234.56"
+fn parse_amount(amount: &str) -> Result<f64, SkipReason> {
+    amount
+        .replace(',', "")
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| SkipReason::InvalidAmount)
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, SkipReason> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| SkipReason::InvalidDate)
+}
+
+fn parse_record(
+    record: csv::StringRecord,
+    origin: &str,
+    account_id: i32,
+    standardizer: &Standardizer,
+) -> Result<BudgetRecord, SkipReason> {
     match origin.to_lowercase().as_str() {
         "usaa" => {
-            if record[1].contains("Capital One"){
+            if record[1].contains("Capital One") {
                 // I know I am up to date on all credit card payments, so we can skip card payments so they don't count towards totals
-                return None
+                return Err(SkipReason::CardPayment)
             }
 
-            let amount = record[4].parse::<f64>().expect("The USAA record must include an amount");
-            let date = NaiveDate::parse_from_str(&record[0], "%Y-%m-%d").expect("The USAA record must include a date");
-            let description = standardize_description(&record[1]);
+            let amount = parse_amount(&record[4])?;
+            let date = parse_date(&record[0])?;
+            let description = standardizer.standardize(&record[1]);
+            let raw_description = record[1].to_string();
+            let location = extract_location(&record[1]).unwrap_or_default();
 
-            let budget_record = BudgetRecord{amount, date, card: String::from("USAA"), description};
-            Some(budget_record)
+            let budget_record = BudgetRecord{amount, date, account_id, description, raw_description, location};
+            Ok(budget_record)
         },
         "capitalone" => {
             let amount: f64;
             // If there is a credit amount, check whether it is a cash back or whether it is a payment onto the card
             if !record[6].is_empty() {
                 if &record[3] == "CREDIT-CASH BACK REWARD"{
-                    amount = record[6].parse::<f64>().expect("The Capital One record must include an amount");
+                    amount = parse_amount(&record[6])?;
                 }
                 else{
                     // I know I am up to date on all credit card payments, so we can skip card payments so they don't count towards totals
-                    return None
+                    return Err(SkipReason::CardPayment)
                 }
             }
             else {
                 // Negative so that we normalize income/spend notation
-                amount = -record[5].parse::<f64>().expect("The Capital One record must include an amount");
+                amount = -parse_amount(&record[5])?;
             }
 
-            let date = NaiveDate::parse_from_str(&record[0], "%Y-%m-%d").expect("The Capital One record must include a date.");
-            let description = standardize_description(&record[3]);
+            let date = parse_date(&record[0])?;
+            let description = standardizer.standardize(&record[3]);
+            let raw_description = record[3].to_string();
+            let location = extract_location(&record[3]).unwrap_or_default();
 
-            let budget_record = BudgetRecord{amount, date, card: String::from("CapitalOne"), description};
-            Some(budget_record)
+            let budget_record = BudgetRecord{amount, date, account_id, description, raw_description, location};
+            Ok(budget_record)
         },
         _ => panic!("You have entered an unknown origin. Options are 'usaa' or 'capitalone'. Your input: {origin}")
     }
 }
 
-fn read_budget_file(path: &PathBuf) -> Result<Vec<BudgetRecord>, Box<dyn Error>> {
+// '-' reads the budget file from stdin
+fn open_budget_file(path: &Path) -> Result<Box<dyn io::Read>, Box<dyn Error>> {
+    if path == Path::new("-") {
+        return Ok(Box::new(io::stdin()));
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+fn read_budget_file(
+    path: &Path,
+    format: Option<&str>,
+    accounts: &mut AccountResolver,
+    standardizer: &Standardizer,
+) -> Result<BudgetFile, Box<dyn Error>> {
     let mut ret: Vec<BudgetRecord> = Vec::new();
+    let mut skipped: Vec<SkippedRow> = Vec::new();
 
-    let file = File::open(&path)?;
-    let mut rdr = csv::ReaderBuilder::new().from_reader(file);
+    let file = open_budget_file(path)?;
+    // The header row is read by hand, since a file given with a format may start with a record
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(file);
+    let mut rows = rdr.records();
+    let first_row = rows.next().transpose()?;
 
-    // Figure out if the budget file is from USAA or Capital One
+    // Figure out if the budget file is from USAA or Capital One, unless the format was given
     // USAA Headers: Date,Description,Original Description,Category,Amount,Status
     // Capital One Headers: Transaction Date,Posted Date,Card No.,Description,Category,Debit,Credit
-    let headers = rdr.headers()?;
-    let origin: String;
-    if &headers[0].to_lowercase() == "date" {
-        origin = "usaa".to_string();
-    } else if &headers[0].to_lowercase() == "transaction date" {
-        origin = "capitalone".to_string();
-    } else {
+    let first_header = first_row
+        .as_ref()
+        .and_then(|row| row.get(0))
+        .unwrap_or_default();
+    let detected = match first_header.trim().to_lowercase().as_str() {
+        "date" => Some("usaa"),
+        "transaction date" => Some("capitalone"),
+        _ => None,
+    };
+    let origin = match (format, detected) {
+        (Some(format), Some(detected)) if format != detected => {
+            return Err(Box::new(InvalidHeader {
+                message: format!(
+                    "Budget file {:?} was given as a {format} file, but its header is a {detected} one",
+                    path
+                ),
+            }));
+        }
+        (Some(format), _) => format.to_string(),
+        (None, Some(detected)) => detected.to_string(),
+        (None, None) => {
+            return Err(Box::new(InvalidHeader {
+                message: format!(
+                    "Unknown header type found on file {:?}. First header: {:?}",
+                    path, first_header
+                ),
+            }));
+        }
+    };
+
+    // The records are read by column position, so a file with fewer columns can't be of this origin
+    let columns = match origin.as_str() {
+        "capitalone" => 7,
+        _ => 5,
+    };
+    let width = first_row.as_ref().map_or(columns, csv::StringRecord::len);
+    if width < columns {
         return Err(Box::new(InvalidHeader {
             message: format!(
-                "Unknown header type found on file {:?}. First header: {:?}",
-                path, &headers[0]
-            )
-            .into(),
+                "Budget file {:?} has {width} column(s), but {origin} files have at least {columns}",
+                path
+            ),
         }));
     }
 
-    for csv_record in rdr.records() {
+    // Without a known header the first row is already a record
+    let first_record = first_row.filter(|_| detected.is_none()).map(Ok);
+    for csv_record in first_record.into_iter().chain(rows) {
         let raw_record = csv_record?;
-        let budget_record = parse_record(raw_record, &origin);
+        let line = raw_record.position().map_or(0, |position| position.line());
+        let skip = |reason: SkipReason| SkippedRow {
+            line,
+            row: raw_record.iter().collect::<Vec<&str>>().join(","),
+            reason,
+        };
+
+        // Capital One exports can mix several cards, so the account is resolved per record
+        let last_four = match origin.as_str() {
+            "capitalone" => Some(&raw_record[2]),
+            _ => None,
+        };
+        let Some(account_id) = accounts.resolve(&origin, path, last_four) else {
+            skipped.push(skip(match accounts.is_interactive() {
+                true => SkipReason::SkippedAccount,
+                false => SkipReason::UnregisteredAccount,
+            }));
+            continue;
+        };
+
+        let budget_record = parse_record(raw_record.clone(), &origin, account_id, standardizer);
         match budget_record {
-            Some(budget_record) => ret.push(budget_record),
-            _ => continue,
+            Ok(budget_record) => ret.push(budget_record),
+            Err(reason) => skipped.push(skip(reason)),
         }
     }
     println!("Found {} budget records: {}", origin, ret.len());
 
-    Ok(ret)
+    Ok(BudgetFile {
+        origin,
+        records: ret,
+        skipped,
+    })
 }
 
 fn setup() -> Result<(PathBuf, PathBuf, serde_json::Value), Box<dyn Error>> {
@@ -269,35 +452,82 @@ fn setup() -> Result<(PathBuf, PathBuf, serde_json::Value), Box<dyn Error>> {
         }
     };
 
-    // Grab secret config
-    let secret_config: serde_json::Value =
-        serde_json::from_reader(File::open(cwd.join("config/secret_config.json"))?)?;
+    // Grab secret config. It is optional when the database is configured through DATABASE_URL or libpq
+    let secret_config_path = cwd.join("config/secret_config.json");
+    let secret_config: serde_json::Value = if secret_config_path.exists() {
+        serde_json::from_reader(File::open(secret_config_path)?)?
+    } else {
+        serde_json::Value::Null
+    };
 
     // Grab the files to be processed
 
     Ok((cwd, fp, secret_config))
 }
 
-fn main() {
-    // Grab the setup information and ensure it is valid
-    let setup = setup();
-    let (cwd, fp, secret_config) = match setup {
-        Ok((cwd, fp, secret_config)) => (cwd, fp, secret_config),
-        Err(e) => panic!("There was an error during setup. Error: {e}"),
-    };
+// Load the standardization rules and warn about any that can never apply
+fn try_load_standardizer(path: &Path) -> Result<Standardizer, String> {
+    let standardizer = Standardizer::load(path)
+        .map_err(|e| format!("There was an error loading the standardization rules. Error: {e}"))?;
+    for conflict in standardizer.conflicts() {
+        println!("Warning: conflicting standardization rules. {conflict}");
+    }
+    Ok(standardizer)
+}
 
-    println!("CWD: {:?} | File Path: {:?}", cwd, fp);
+fn load_standardizer(path: &Path) -> Standardizer {
+    try_load_standardizer(path).unwrap_or_else(|e| panic!("{e}"))
+}
 
-    // Grab any new budget files to process
-    let budget_files_to_process = fs::read_dir(&fp.join("new/"));
-    let budget_files_to_process = match budget_files_to_process {
-        Ok(budget_files_to_process) => {
-            let budget_files: Vec<fs::DirEntry> = budget_files_to_process
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| entry.path().is_file())
-                .collect();
-            budget_files
+fn add_rule(cwd: &Path, rule: StandardizationRule) {
+    let path = cwd.join(STANDARDIZATION_RULES_PATH);
+    let described = rule.to_string();
+    let standardizer = match load_standardizer(&path).with_rule(rule) {
+        Ok(standardizer) => standardizer,
+        Err(e) => {
+            println!("The rule {described} is not valid. Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let new_rule = standardizer.rules().len() - 1;
+    for conflict in standardizer.conflicts() {
+        if conflict.loser == new_rule || conflict.winner == new_rule {
+            println!("Warning: the new rule conflicts with an existing one. {conflict}");
         }
+    }
+    match standardizer.save(&path) {
+        Ok(_) => println!("Added rule {described} to {:?}", path),
+        Err(e) => panic!("There was an error saving the standardization rules. Error: {e}"),
+    }
+}
+
+// Both ends of the terminal are needed to prompt, so cron and systemd runs are never interactive
+fn has_terminal() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+// How an import runs
+struct ImportOptions {
+    // Whether there is someone to register accounts and tag descriptions
+    interactive: bool,
+    // Only show what the import would do
+    dry_run: bool,
+    // Read every file as this origin instead of detecting it from the header
+    format: Option<&'static str>,
+    // Delete the files once their records are stored
+    remove: bool,
+}
+
+// The budget files waiting in new/
+fn new_budget_files(fp: &Path) -> Vec<PathBuf> {
+    let budget_files_to_process = fs::read_dir(fp.join("new/"));
+    match budget_files_to_process {
+        Ok(budget_files_to_process) => budget_files_to_process
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| entry.path())
+            .collect(),
         Err(e) => match e.kind() {
             ErrorKind::NotFound => panic!(
                 "There is no 'new/' directory available at filepath {:?}",
@@ -305,11 +535,30 @@ fn main() {
             ),
             _ => panic!("There was an error determining the files to process. Error: {e}"),
         },
+    }
+}
+
+/// Import `paths`, or the new budget files in new/ without any. Without `interactive`, nothing is
+/// asked: files of unregistered accounts are left in place and new descriptions wait in the
+/// untagged queue. A `dry_run` only shows what the import would do.
+fn import(
+    cwd: PathBuf,
+    fp: PathBuf,
+    secret_config: serde_json::Value,
+    paths: Vec<PathBuf>,
+    options: &ImportOptions,
+) {
+    println!("CWD: {:?} | File Path: {:?}", cwd, fp);
+
+    // Grab any new budget files to process
+    let budget_files_to_process = match paths.is_empty() {
+        true => new_budget_files(&fp),
+        false => paths,
     };
 
     if budget_files_to_process.is_empty() {
         println!("There are no new budget files to process.");
-        std::process::exit(0);
+        return;
     }
 
     println!(
@@ -317,26 +566,128 @@ fn main() {
         budget_files_to_process.len()
     );
 
+    // Connect to the postgres database
+    let connection = &mut db::establish_connection(secret_config);
+
+    if let Err(e) = import_files(&cwd, connection, &budget_files_to_process, options) {
+        panic!("{e}");
+    }
+}
+
+// What an import did with its files, for callers that keep track of them
+struct ImportOutcome {
+    records: usize,
+    imported: Vec<PathBuf>,
+    failed: Vec<PathBuf>,
+}
+
+// Read, insert and remove the given budget files, then tag or queue their new descriptions.
+// Failing to load the configuration or to store the records leaves every file in place.
+fn import_files(
+    cwd: &Path,
+    connection: &mut PgConnection,
+    paths: &[PathBuf],
+    options: &ImportOptions,
+) -> Result<ImportOutcome, String> {
+    let interactive = options.interactive;
+    let mut failed: Vec<PathBuf> = Vec::new();
+
     // Process the new budget files
+    let accounts_path = cwd.join("config/accounts.json");
+    let mut accounts = AccountResolver::new(connection, accounts_path, interactive)
+        .map_err(|e| format!("There was an error loading the account rules. Error: {e}"))?;
+    if options.dry_run {
+        accounts.use_placeholders();
+    }
+    let standardizer = try_load_standardizer(&cwd.join(STANDARDIZATION_RULES_PATH))?;
     let mut budget_records: Vec<BudgetRecord> = Vec::new();
     let mut successful_records: Vec<PathBuf> = Vec::new();
+    let mut read_files: Vec<(PathBuf, BudgetFile)> = Vec::new();
 
-    for budget_file in &budget_files_to_process {
-        let path = budget_file.path();
-
-        let record_information = read_budget_file(&path);
-        let records = match record_information {
-            Ok(records) => records,
+    for path in paths {
+        let record_information =
+            read_budget_file(path, options.format, &mut accounts, &standardizer);
+        let file = match record_information {
+            Ok(file) => file,
             Err(e) => {
                 println!(
-                    "There was an error reading budget file {:?}. Error: {:?}",
+                    "There was an error reading budget file {:?}. Error: {}",
                     path, e
                 );
+                failed.push(path.clone());
                 continue;
             }
         };
+        read_files.push((path.clone(), file));
+    }
 
-        budget_records.extend(records);
+    if options.dry_run {
+        let account_names = accounts.account_names();
+        preview::preview(connection, &read_files, &account_names);
+        return Ok(ImportOutcome {
+            records: 0,
+            imported: Vec::new(),
+            failed,
+        });
+    }
+
+    for (path, file) in read_files {
+        // A file that is removed once imported is kept whole while any of its records has no
+        // account, so they aren't lost. They are imported once the account is registered.
+        let removed = options.remove && path.as_path() != Path::new("-");
+        let has_reason = |reason: SkipReason| file.skipped.iter().any(|row| row.reason == reason);
+        if has_reason(SkipReason::UnregisteredAccount) {
+            println!(
+                "There was an error reading budget file {:?}. Error: it has records of an unregistered {} account. Run the import from a terminal to register it",
+                path, file.origin
+            );
+            failed.push(path);
+            continue;
+        }
+        // A row that can't be read would be lost, so the file is kept until it is fixed
+        let invalid: Vec<&SkippedRow> = file
+            .skipped
+            .iter()
+            .filter(|row| {
+                matches!(
+                    row.reason,
+                    SkipReason::InvalidAmount | SkipReason::InvalidDate
+                )
+            })
+            .collect();
+        if !invalid.is_empty() {
+            println!(
+                "There was an error reading budget file {:?}. Error: {} row(s) could not be read",
+                path,
+                invalid.len()
+            );
+            for row in invalid {
+                println!("  line {}: {} ({})", row.line, row.row, row.reason);
+            }
+            failed.push(path);
+            continue;
+        }
+        if has_reason(SkipReason::SkippedAccount) {
+            if removed {
+                println!(
+                    "Budget file {:?} was not imported, since the records of a {} account were skipped. It is kept to import once the account is registered.",
+                    path, file.origin
+                );
+                failed.push(path);
+                continue;
+            }
+            let skipped = file
+                .skipped
+                .iter()
+                .filter(|row| row.reason == SkipReason::SkippedAccount)
+                .count();
+            println!(
+                "Importing the other records of budget file {:?}. {skipped} record(s) of a skipped {} account were left out.",
+                path, file.origin
+            );
+        }
+
+        budget_records.extend(file.records);
         successful_records.push(path);
     }
 
@@ -344,24 +695,29 @@ fn main() {
     println!(
         "Successful record files: {} | Unsuccessful record files: {} ({}% failed)",
         successful_records.len(),
-        (budget_files_to_process.len() - successful_records.len()),
-        ((1 - (successful_records.len() / budget_files_to_process.len())) * 100)
+        (paths.len() - successful_records.len()),
+        ((1 - (successful_records.len() / paths.len())) * 100)
     );
 
-    // Connect to the postgres database
-    let connection = &mut db::establish_connection(secret_config);
-
     // Insert the newly found records
     if !budget_records.is_empty() {
-        let result = db::insert_records(connection, &budget_records);
-        match result {
-            Ok(_) => (),
-            Err(e) => panic!("There was an error inserting the new records. Error: {e}"),
+        let result = db::insert_records(connection, &budget_records, |inserted, total| {
+            print!("\rInserted {inserted}/{total} records");
+            io::stdout().flush().unwrap();
+        });
+        println!();
+        if let Err(e) = result {
+            return Err(format!(
+                "There was an error inserting the new records. Error: {e}"
+            ));
         }
     }
 
-    // Delete the processed files
-    for path in &successful_records {
+    // Delete the processed files. Stdin has nothing to delete.
+    let removable = successful_records
+        .iter()
+        .filter(|path| options.remove && path.as_path() != Path::new("-"));
+    for path in removable {
         match fs::remove_file(path) {
             Ok(_) => (),
             Err(e) => println!("Failed to remove file {:?}. Error: {:?}", path, e),
@@ -369,73 +725,97 @@ fn main() {
     }
 
     // Grab the unique standardized descriptions
-    let mut unique_descriptions: HashSet<&String> = HashSet::new();
-    for budget_record in &budget_records {
-        unique_descriptions.insert(&budget_record.description);
-    }
+    let unique_descriptions: HashSet<&String> = budget_records
+        .iter()
+        .map(|budget_record| &budget_record.description)
+        .collect();
     println!("Unique Descriptions: {}", unique_descriptions.len());
 
-    // Remove the descriptions that already have information stored for them
-    let descriptions = db::select_descriptions(connection);
-    for description in descriptions {
-        unique_descriptions.remove(&description.description);
+    // Work through every untagged description, including the ones skipped in earlier sessions.
+    // Headless imports leave them in the queue for 'budget tag'.
+    if interactive {
+        tag(connection);
+    } else {
+        match db::select_untagged_descriptions(connection) {
+            Ok(untagged) => println!(
+                "{} description(s) are waiting to be tagged with 'budget tag'.",
+                untagged.len()
+            ),
+            Err(e) => println!("Counting the untagged descriptions failed. Error: {e}"),
+        }
     }
 
-    println!("Unique Descriptions: {}", unique_descriptions.len());
-    let mut upload_descriptions: Vec<UploadDescription> = Vec::new();
-    // Request information on the descriptions that remain
-    println!("Requesting information on descriptions that have not been seen before.");
-    println!("Press CRTL + S to skip the current description, and CRTL + A to skip all the remaining descriptions.");
-
-    enable_raw_mode().unwrap();
-    'outer: for description in unique_descriptions {
-        println!("\r");
-        println!("Please provide primary information for description '{description}':\r");
-        let primary_information = match description_input_parser() {
-            Some(s) if s == "CRTL+A_ABORT" => break 'outer,
-            Some(s) => s,
-            None => continue, // CRTL+S
-        };
+    // Very very basic initial analysis
+    let mut spending_total: f64 = 0.00;
+    let mut income_total: f64 = 0.00;
+    let mut spending_by_location: BTreeMap<String, f64> = BTreeMap::new();
+    for budget_record in &budget_records {
+        if budget_record.amount < 0.00 {
+            spending_total += budget_record.amount;
+            let location = match budget_record.location.to_string() {
+                location if location.is_empty() => "Unknown".to_string(),
+                location => location,
+            };
+            *spending_by_location.entry(location).or_default() += budget_record.amount;
+        } else {
+            income_total += budget_record.amount;
+        }
+        // println!("Individual record: {:?}", budget_record)
+    }
+    println!("Income total: {income_total:.2}");
+    println!("Spending total: {spending_total:.2}");
+    let difference = spending_total + income_total;
+    println!("Difference: {difference:.2}");
 
-        println!("\r");
-        println!("Please provide secondary information if it exists:\r");
-        let secondary_information = match description_input_parser() {
-            Some(s) if s == "CRTL+A_ABORT" => break 'outer,
-            Some(s) => s,
-            None => continue, // CRTL+S
-        };
+    println!("Spending by location:");
+    for (location, total) in &spending_by_location {
+        println!("  {location}: {total:.2}");
+    }
 
-        println!("\r");
-        println!("Please provide tertiary information if it exists:\r");
-        let tertiary_information = match description_input_parser() {
-            Some(s) if s == "CRTL+A_ABORT" => break 'outer,
-            Some(s) => s,
-            None => continue, // CRTL+S
-        };
+    Ok(ImportOutcome {
+        records: budget_records.len(),
+        imported: successful_records,
+        failed,
+    })
+}
 
-        println!("\r");
-        println!("Please provide additional information if it exists:\r");
-        let additional_information = match description_input_parser() {
-            Some(s) if s == "CRTL+A_ABORT" => break 'outer,
-            Some(s) => s,
-            None => continue, // CRTL+S
-        };
+// Tag the descriptions in the untagged queue, most spent on first
+fn tag(connection: &mut PgConnection) {
+    let untagged = match db::select_untagged_descriptions(connection) {
+        Ok(untagged) => untagged,
+        Err(e) => panic!("There was an error loading the untagged descriptions. Error: {e}"),
+    };
+    if untagged.is_empty() {
+        println!("There are no untagged descriptions.");
+        return;
+    }
+    println!("{} description(s) waiting to be tagged.", untagged.len());
 
-        let upload_description = UploadDescription {
-            description: description.to_string(),
-            primary_information,
-            secondary_information,
-            tertiary_information,
-            additional_information,
+    // Learn from the descriptions tagged so far to suggest categories for the new ones
+    let descriptions = db::select_descriptions(connection);
+    let mut classifier = CategoryClassifier::train(&descriptions);
+    let tagged: Vec<UploadDescription> = descriptions.iter().map(UploadDescription::from).collect();
+
+    let pending: Vec<UploadDescription> = untagged
+        .into_iter()
+        .map(|description| UploadDescription {
+            description,
+            ..Default::default()
+        })
+        .collect();
+    let spend = match db::select_description_spend(connection) {
+        Ok(spend) => spend,
+        Err(e) => panic!("There was an error loading the spend per description. Error: {e}"),
+    };
+    let title = "Tagging untagged descriptions";
+    let mut upload_descriptions =
+        match tagging::tag_descriptions(title, pending, &tagged, &mut classifier, &spend) {
+            Ok(upload_descriptions) => upload_descriptions,
+            Err(e) => panic!("There was an error while tagging the new descriptions. Error: {e}"),
         };
 
-        println!("\r");
-        println!("Description for upload: {:?}\n\n", upload_description);
-        upload_descriptions.push(upload_description);
-    }
-    disable_raw_mode().unwrap();
-
-    println!("{:?}", upload_descriptions);
+    // Link the new descriptions into the category tree, reusing the stored spelling of known categories
+    categories::link_descriptions(connection, &mut upload_descriptions);
 
     // Upload the new description information
     let result = db::insert_description(connection, &upload_descriptions);
@@ -444,19 +824,169 @@ fn main() {
         Err(e) => panic!("There was an error uploading the new description information to the database. Error: {e}")
     }
 
-    // Very very basic initial analysis
-    let mut spending_total: f64 = 0.00;
-    let mut income_total: f64 = 0.00;
-    for budget_record in budget_records {
-        if budget_record.amount < 0.00 {
-            spending_total += budget_record.amount;
-        } else {
-            income_total += budget_record.amount;
+    match db::select_untagged_descriptions(connection) {
+        Ok(remaining) => println!(
+            "Tagged {} description(s). {} remain in the queue for 'budget tag'.",
+            upload_descriptions.len(),
+            remaining.len()
+        ),
+        Err(e) => println!(
+            "Tagged {} description(s), but counting the remaining ones failed. Error: {e}",
+            upload_descriptions.len()
+        ),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Grab the setup information and ensure it is valid
+    let setup = setup();
+    let (cwd, fp, secret_config) = match setup {
+        Ok((cwd, fp, secret_config)) => (cwd, fp, secret_config),
+        Err(e) => panic!("There was an error during setup. Error: {e}"),
+    };
+
+    let interactive = !cli.headless && has_terminal();
+    let require_terminal = |command: &str| {
+        if !interactive {
+            println!("'budget {command}' needs a terminal. Run it from an interactive shell.");
+            std::process::exit(1);
+        }
+    };
+
+    let command = cli.command.unwrap_or(Command::Import {
+        paths: Vec::new(),
+        format: None,
+        remove: false,
+        dry_run: false,
+    });
+    match command {
+        Command::Import {
+            paths,
+            format,
+            remove,
+            dry_run,
+        } => {
+            if paths.iter().any(|path| path == Path::new("-")) && format.is_none() {
+                println!("Reading a budget file from stdin ('-') needs --format usaa or --format capitalone.");
+                std::process::exit(1);
+            }
+            if !cli.headless && !interactive {
+                println!("No terminal detected, importing headless.");
+            }
+            let options = ImportOptions {
+                interactive: interactive && !dry_run,
+                dry_run,
+                format: format.map(Format::origin),
+                // Files in new/ are always cleared once imported, explicit ones only when asked
+                remove: paths.is_empty() || remove,
+            };
+            import(cwd, fp, secret_config, paths, &options);
+        }
+        Command::Watch { interval } => {
+            watch::watch(&cwd, &fp, &secret_config, Duration::from_secs(interval));
+        }
+        Command::Tag => {
+            require_terminal("tag");
+            let connection = &mut db::establish_connection(secret_config);
+            tag(connection);
+        }
+        Command::Report { filter, by } => {
+            let connection = &mut db::establish_connection(secret_config);
+            report::report(connection, &filter.to_filter(), by);
+        }
+        Command::Export { filter, output } => {
+            let connection = &mut db::establish_connection(secret_config);
+            report::export(connection, &filter.to_filter(), output.as_deref());
+        }
+        Command::Query {
+            term,
+            filter,
+            limit,
+        } => {
+            let filter = RecordFilter {
+                term,
+                limit: Some(limit),
+                ..filter.to_filter()
+            };
+            let connection = &mut db::establish_connection(secret_config);
+            report::query(connection, &filter);
+        }
+        Command::Categories => {
+            require_terminal("categories");
+            let connection = &mut db::establish_connection(secret_config);
+            categories::migrate_descriptions(connection);
+        }
+        Command::AddRule(rule) => add_rule(&cwd, rule.into_rule()),
+        Command::TestRule(rule) => {
+            let rules_path = cwd.join(STANDARDIZATION_RULES_PATH);
+            let standardizer = load_standardizer(&rules_path);
+            let connection = &mut db::establish_connection(secret_config);
+            rule_bench::test_rule(connection, &standardizer, rule.into_rule(), &rules_path);
+        }
+        Command::Restandardize => {
+            let standardizer = load_standardizer(&cwd.join(STANDARDIZATION_RULES_PATH));
+            let connection = &mut db::establish_connection(secret_config);
+            restandardize::restandardize(connection, &standardizer);
+        }
+        Command::Descriptions { command } => {
+            let connection = &mut db::establish_connection(secret_config);
+            match command {
+                DescriptionsCommand::Search { term } => descriptions::show(connection, &term),
+                DescriptionsCommand::Edit { term } => {
+                    require_terminal("descriptions edit");
+                    descriptions::edit(connection, &term);
+                }
+                DescriptionsCommand::Delete { description } => {
+                    descriptions::delete(connection, &description)
+                }
+                DescriptionsCommand::Export { file, all } => {
+                    bulk::export_descriptions(connection, &file, all)
+                }
+                DescriptionsCommand::Import { file } => {
+                    bulk::import_descriptions(connection, &file)
+                }
+            }
         }
-        // println!("Individual record: {:?}", budget_record)
     }
-    println!("Income total: {income_total:.2}");
-    println!("Spending total: {spending_total:.2}");
-    let difference = spending_total + income_total;
-    println!("Difference: {difference:.2}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capital_one(debit: &str, credit: &str) -> csv::StringRecord {
+        csv::StringRecord::from(vec![
+            "2024-03-01",
+            "2024-03-02",
+            "1234",
+            "KING SOOPERS #0123 DENVER CO",
+            "Groceries",
+            debit,
+            credit,
+        ])
+    }
+
+    fn parse(record: csv::StringRecord, origin: &str) -> Result<BudgetRecord, SkipReason> {
+        let standardizer = Standardizer::new(Vec::new()).unwrap();
+        parse_record(record, origin, 1, &standardizer)
+    }
+
+    #[test]
+    fn amounts_with_thousands_separators() {
+        let record = parse(capital_one("1,234.56", ""), "capitalone").unwrap();
+        assert_eq!(record.amount, -1234.56);
+    }
+
+    #[test]
+    fn unreadable_rows_are_skipped() {
+        assert_eq!(
+            parse(capital_one("", ""), "capitalone").err(),
+            Some(SkipReason::InvalidAmount)
+        );
+
+        let usaa = csv::StringRecord::from(vec!["03/01/2024", "NETFLIX", "NETFLIX", "", "-15.49"]);
+        assert_eq!(parse(usaa, "usaa").err(), Some(SkipReason::InvalidDate));
+    }
 }
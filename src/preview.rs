@@ -0,0 +1,229 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
+
+use chrono::NaiveDate;
+use diesel::PgConnection;
+
+use crate::{database::db, BudgetFile, BudgetRecord};
+
+// Amounts are compared in cents, so float rounding can't hide a duplicate
+type RecordKey = (i32, NaiveDate, i64, String);
+
+fn key(account_id: i32, date: NaiveDate, amount: f64, raw_description: &str) -> RecordKey {
+    (
+        account_id,
+        date,
+        (amount * 100.0).round() as i64,
+        raw_description.to_string(),
+    )
+}
+
+// Keys of the stored records in the period the files cover
+fn stored_keys(connection: &mut PgConnection, records: &[&BudgetRecord]) -> HashSet<RecordKey> {
+    let first = records.iter().map(|record| record.date).min();
+    let last = records.iter().map(|record| record.date).max();
+    let (Some(first), Some(last)) = (first, last) else {
+        return HashSet::new();
+    };
+    match db::select_record_keys(connection, first, last) {
+        Ok(keys) => keys
+            .into_iter()
+            .map(|(account_id, date, amount, raw)| key(account_id, date, amount, &raw))
+            .collect(),
+        Err(e) => panic!("There was an error loading the stored records. Error: {e}"),
+    }
+}
+
+// Label each record that is already stored, or that an earlier record of these files repeats
+fn duplicate_labels(records: &[&BudgetRecord], stored: &HashSet<RecordKey>) -> Vec<&'static str> {
+    let mut seen: HashSet<RecordKey> = HashSet::new();
+    records
+        .iter()
+        .map(|record| {
+            let record_key = key(
+                record.account_id,
+                record.date,
+                record.amount,
+                &record.raw_description,
+            );
+            if stored.contains(&record_key) {
+                " [duplicate of a stored record]"
+            } else if !seen.insert(record_key) {
+                " [duplicate within these files]"
+            } else {
+                ""
+            }
+        })
+        .collect()
+}
+
+/// Show what importing the files would do: the records each would add with their standardized
+/// descriptions, the rows left out and why, duplicates and descriptions that still need tagging.
+/// Only reads from the database.
+pub fn preview(
+    connection: &mut PgConnection,
+    files: &[(PathBuf, BudgetFile)],
+    account_names: &HashMap<i32, String>,
+) {
+    let records: Vec<&BudgetRecord> = files.iter().flat_map(|(_, file)| &file.records).collect();
+    let stored = stored_keys(connection, &records);
+    let mut labels = duplicate_labels(&records, &stored).into_iter();
+    let (mut skipped, mut duplicates) = (0, 0);
+
+    for (path, file) in files {
+        println!();
+        println!(
+            "{:?} ({}): {} record(s), {} skipped row(s)",
+            path,
+            file.origin,
+            file.records.len(),
+            file.skipped.len()
+        );
+        for record in &file.records {
+            let duplicate = labels.next().unwrap_or_default();
+            if !duplicate.is_empty() {
+                duplicates += 1;
+            }
+
+            let location = match record.location.to_string() {
+                location if location.is_empty() => String::new(),
+                location => format!(" ({location})"),
+            };
+            println!(
+                "  {} {:>10.2} {:<12} {} -> {}{location}{duplicate}",
+                record.date,
+                record.amount,
+                account_names
+                    .get(&record.account_id)
+                    .map_or("?", String::as_str),
+                record.raw_description,
+                record.description
+            );
+        }
+        for row in &file.skipped {
+            skipped += 1;
+            println!("  skipped line {}: {} ({})", row.line, row.row, row.reason);
+        }
+    }
+
+    // Descriptions without information yet, and whether they were ever seen before
+    let tagged: HashSet<String> = db::select_descriptions(connection)
+        .into_iter()
+        .map(|description| description.description)
+        .collect();
+    let queued: HashSet<String> = match db::select_untagged_descriptions(connection) {
+        Ok(queued) => queued.into_iter().collect(),
+        Err(e) => panic!("There was an error loading the untagged descriptions. Error: {e}"),
+    };
+    let mut untagged: BTreeMap<&str, usize> = BTreeMap::new();
+    for record in &records {
+        if !tagged.contains(&record.description) {
+            *untagged.entry(&record.description).or_default() += 1;
+        }
+    }
+
+    println!();
+    println!(
+        "{} record(s) would be imported from {} file(s). {skipped} row(s) skipped, {duplicates} duplicate(s).",
+        records.len(),
+        files.len()
+    );
+    if !untagged.is_empty() {
+        println!("{} description(s) would need tagging:", untagged.len());
+        for (description, count) in &untagged {
+            let status = match queued.contains(*description) {
+                true => "already waiting to be tagged",
+                false => "new",
+            };
+            println!("  {description}: {count} record(s), {status}");
+        }
+    }
+    println!("Dry run: nothing was written to the database and no files were removed.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    #[test]
+    fn amounts_are_compared_in_cents() {
+        assert_eq!(
+            key(1, date(14), 0.1 + 0.2, "SQ *BLUE BOTTLE"),
+            key(1, date(14), 0.3, "SQ *BLUE BOTTLE")
+        );
+        assert_eq!(
+            key(1, date(14), -12.340000001, "TARGET"),
+            key(1, date(14), -12.34, "TARGET")
+        );
+        assert_ne!(
+            key(1, date(14), -12.34, "TARGET"),
+            key(1, date(14), -12.35, "TARGET")
+        );
+    }
+
+    #[test]
+    fn every_field_is_part_of_the_key() {
+        let original = key(1, date(14), -4.5, "SQ *BLUE BOTTLE 0042");
+        assert_ne!(original, key(2, date(14), -4.5, "SQ *BLUE BOTTLE 0042"));
+        assert_ne!(original, key(1, date(15), -4.5, "SQ *BLUE BOTTLE 0042"));
+        assert_ne!(original, key(1, date(14), 4.5, "SQ *BLUE BOTTLE 0042"));
+        assert_ne!(original, key(1, date(14), -4.5, "SQ *BLUE BOTTLE 0043"));
+    }
+
+    fn record(account_id: i32, day: u32, amount: f64, raw_description: &str) -> BudgetRecord {
+        BudgetRecord {
+            amount,
+            date: date(day),
+            account_id,
+            description: raw_description.to_lowercase(),
+            raw_description: raw_description.to_string(),
+            location: Default::default(),
+        }
+    }
+
+    #[test]
+    fn duplicates_of_stored_records_and_between_files() {
+        let stored = HashSet::from([key(1, date(14), -4.5, "SQ *BLUE BOTTLE")]);
+        // The second file repeats the first, and the first starts with an imported record
+        let first = [
+            record(1, 14, -4.5, "SQ *BLUE BOTTLE"),
+            record(1, 15, -12.34, "TARGET"),
+        ];
+        let second = [
+            record(1, 15, -12.34, "TARGET"),
+            record(1, 16, -3.0, "TARGET"),
+        ];
+        let records: Vec<&BudgetRecord> = first.iter().chain(&second).collect();
+        assert_eq!(
+            duplicate_labels(&records, &stored),
+            vec![
+                " [duplicate of a stored record]",
+                "",
+                " [duplicate within these files]",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn placeholder_accounts_only_repeat_each_other() {
+        // An unregistered card has a negative placeholder id, shared by every file it appears in
+        let stored = HashSet::from([key(1, date(14), -4.5, "SQ *BLUE BOTTLE")]);
+        let records = [
+            record(-1, 14, -4.5, "SQ *BLUE BOTTLE"),
+            record(-1, 14, -4.5, "SQ *BLUE BOTTLE"),
+            record(-2, 14, -4.5, "SQ *BLUE BOTTLE"),
+        ];
+        let records: Vec<&BudgetRecord> = records.iter().collect();
+        assert_eq!(
+            duplicate_labels(&records, &stored),
+            vec!["", " [duplicate within these files]", ""]
+        );
+    }
+}
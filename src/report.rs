@@ -0,0 +1,197 @@
+use std::{collections::HashMap, error::Error, io, path::Path};
+
+use clap::ValueEnum;
+use diesel::PgConnection;
+
+use crate::database::{
+    db,
+    models::{RecordDetail, RecordFilter},
+};
+
+const EXPORT_COLUMNS: [&str; 12] = [
+    "date",
+    "amount",
+    "account",
+    "description",
+    "raw_description",
+    "city",
+    "state",
+    "country",
+    "primary_information",
+    "secondary_information",
+    "tertiary_information",
+    "additional_information",
+];
+
+/// What spending is broken down by in a report
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Grouping {
+    Category,
+    Location,
+    Account,
+    Month,
+}
+
+impl Grouping {
+    fn group(self, record: &RecordDetail) -> String {
+        let group = match self {
+            Grouping::Category => record
+                .primary_information
+                .iter()
+                .chain(&record.secondary_information)
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(" > "),
+            Grouping::Location => [&record.city, &record.state, &record.country]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(", "),
+            Grouping::Account => record.account.clone(),
+            Grouping::Month => record.date.format("%Y-%m").to_string(),
+        };
+        match group {
+            group if group.is_empty() => match self {
+                Grouping::Category => "Untagged".to_string(),
+                _ => "Unknown".to_string(),
+            },
+            group => group,
+        }
+    }
+}
+
+fn load_records(connection: &mut PgConnection, filter: &RecordFilter) -> Vec<RecordDetail> {
+    match db::select_record_details(connection, filter) {
+        Ok(records) => records,
+        Err(e) => panic!("There was an error loading the records. Error: {e}"),
+    }
+}
+
+fn describe_period(filter: &RecordFilter) -> String {
+    match (filter.from, filter.to) {
+        (Some(from), Some(to)) => format!(" from {from} to {to}"),
+        (Some(from), None) => format!(" since {from}"),
+        (None, Some(to)) => format!(" until {to}"),
+        (None, None) => String::new(),
+    }
+}
+
+/// Income and spending totals of the stored records, with the spending broken down by `grouping`
+pub fn report(connection: &mut PgConnection, filter: &RecordFilter, grouping: Grouping) {
+    let records = load_records(connection, filter);
+    if records.is_empty() {
+        println!("There are no records{}.", describe_period(filter));
+        return;
+    }
+
+    let mut spending_total: f64 = 0.00;
+    let mut income_total: f64 = 0.00;
+    let mut spending_by_group: HashMap<String, (f64, usize)> = HashMap::new();
+    for record in &records {
+        if record.amount < 0.00 {
+            spending_total += record.amount;
+            let (total, count) = spending_by_group.entry(grouping.group(record)).or_default();
+            *total += record.amount;
+            *count += 1;
+        } else {
+            income_total += record.amount;
+        }
+    }
+
+    println!("{} record(s){}", records.len(), describe_period(filter));
+    println!("Income total: {income_total:.2}");
+    println!("Spending total: {spending_total:.2}");
+    let difference = spending_total + income_total;
+    println!("Difference: {difference:.2}");
+
+    // Largest spending first
+    let mut groups: Vec<(String, (f64, usize))> = spending_by_group.into_iter().collect();
+    groups.sort_by(|a, b| a.1 .0.total_cmp(&b.1 .0).then_with(|| a.0.cmp(&b.0)));
+    println!("Spending by {}:", format!("{grouping:?}").to_lowercase());
+    for (group, (total, count)) in &groups {
+        println!("  {group}: {total:.2} ({count} record(s))");
+    }
+}
+
+/// List the records matching `filter`, newest first
+pub fn query(connection: &mut PgConnection, filter: &RecordFilter) {
+    let records = load_records(connection, filter);
+    if records.is_empty() {
+        println!("No records match.");
+        return;
+    }
+
+    for record in &records {
+        let information = [
+            &record.primary_information,
+            &record.secondary_information,
+            &record.tertiary_information,
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|name| !name.is_empty())
+        .cloned()
+        .collect::<Vec<String>>()
+        .join(" > ");
+        println!(
+            "{} {:>10.2} {:<12} {} [{}]",
+            record.date,
+            record.amount,
+            record.account,
+            record.description,
+            match information.as_str() {
+                "" => "untagged",
+                information => information,
+            }
+        );
+        if let Some(raw_description) = &record.raw_description {
+            if *raw_description != record.description {
+                println!("{:>24}{raw_description}", "");
+            }
+        }
+    }
+    let total: f64 = records.iter().map(|record| record.amount).sum();
+    println!("{} record(s), totaling {total:.2}", records.len());
+}
+
+fn write_records(writer: impl io::Write, records: &[RecordDetail]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(EXPORT_COLUMNS)?;
+    for record in records {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        writer.write_record([
+            record.date.to_string(),
+            format!("{:.2}", record.amount),
+            record.account.clone(),
+            record.description.clone(),
+            optional(&record.raw_description),
+            optional(&record.city),
+            optional(&record.state),
+            optional(&record.country),
+            optional(&record.primary_information),
+            optional(&record.secondary_information),
+            optional(&record.tertiary_information),
+            optional(&record.additional_information),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the records matching `filter` as CSV to `output`, or to stdout without one
+pub fn export(connection: &mut PgConnection, filter: &RecordFilter, output: Option<&Path>) {
+    let records = load_records(connection, filter);
+    let result = match output {
+        Some(path) => std::fs::File::create(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|file| write_records(file, &records)),
+        None => write_records(io::stdout().lock(), &records),
+    };
+    match (result, output) {
+        (Ok(_), Some(path)) => println!("Exported {} record(s) to {:?}.", records.len(), path),
+        (Ok(_), None) => (),
+        (Err(e), _) => panic!("There was an error exporting the records. Error: {e}"),
+    }
+}
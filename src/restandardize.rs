@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use diesel::{Connection, PgConnection, QueryResult};
+
+use crate::{confirm, database::db, standardize::Standardizer, UploadDescription};
+
+/// Re-run the current standardization rules over every stored record. Shows what would change,
+/// then updates the records and carries the description information over to their new descriptions.
+pub fn restandardize(connection: &mut PgConnection, standardizer: &Standardizer) {
+    let records = match db::select_record_descriptions(connection) {
+        Ok(records) => records,
+        Err(e) => panic!("There was an error loading the records. Error: {e}"),
+    };
+
+    // Record ids grouped by the description change they would get
+    let mut changes: BTreeMap<(String, String), Vec<i64>> = BTreeMap::new();
+    let mut without_raw = 0;
+    for (id, raw_description, description) in records {
+        // Records imported before raw descriptions were kept can only be re-run from their standardized form
+        let source = raw_description.unwrap_or_else(|| {
+            without_raw += 1;
+            description.clone()
+        });
+        let standardized = standardizer.standardize(&source);
+        if standardized != description {
+            changes
+                .entry((description, standardized))
+                .or_default()
+                .push(id);
+        }
+    }
+
+    if without_raw > 0 {
+        println!("{without_raw} record(s) have no raw description stored and were re-run from their standardized description.");
+    }
+    if changes.is_empty() {
+        println!("All records already match the current standardization rules.");
+        return;
+    }
+
+    for ((old_description, new_description), ids) in &changes {
+        println!("- {old_description}");
+        println!("+ {new_description} ({} record(s))", ids.len());
+    }
+    let total: usize = changes.values().map(Vec::len).sum();
+    if !confirm(&format!(
+        "Update {total} record(s) across {} description change(s)?",
+        changes.len()
+    )) {
+        println!("Nothing was changed.");
+        return;
+    }
+
+    let result = connection.transaction(|connection| {
+        let mut merged = Vec::new();
+        for ((_, new_description), ids) in &changes {
+            db::update_record_descriptions(connection, ids, new_description)?;
+        }
+        // The rules may split the records of one description across several new ones
+        let mut targets: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (old_description, new_description) in changes.keys() {
+            targets
+                .entry(old_description)
+                .or_default()
+                .push(new_description);
+        }
+        for (old_description, new_descriptions) in &targets {
+            merged.extend(merge_description(
+                connection,
+                old_description,
+                new_descriptions,
+            )?);
+        }
+        QueryResult::Ok(merged)
+    });
+
+    match result {
+        Ok(merged) => {
+            println!("Updated {total} record(s).");
+            for note in merged {
+                println!("{note}");
+            }
+        }
+        Err(e) => panic!("There was an error re-standardizing the records. Error: {e}"),
+    }
+}
+
+// The information of the old description is copied to every description its records moved to,
+// unless that description is already tagged, in which case the existing tags win. Once no record
+// uses the old description anymore, its information is removed.
+fn merge_description(
+    connection: &mut PgConnection,
+    old_description: &str,
+    new_descriptions: &[&str],
+) -> QueryResult<Vec<String>> {
+    let Some(old) = db::select_description(connection, old_description)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut notes = Vec::new();
+    let mut copies = Vec::new();
+    for &new_description in new_descriptions {
+        if db::select_description(connection, new_description)?.is_some() {
+            notes.push(format!(
+                "Kept the information of '{new_description}' over '{old_description}', it is already tagged."
+            ));
+        } else {
+            copies.push(UploadDescription {
+                description: new_description.to_string(),
+                ..UploadDescription::from(&old)
+            });
+            notes.push(format!(
+                "Copied the information for '{old_description}' to '{new_description}'."
+            ));
+        }
+    }
+    if !copies.is_empty() {
+        db::insert_description(connection, &copies)?;
+    }
+
+    if db::count_records_with_description(connection, old_description)? == 0 {
+        db::delete_description(connection, old_description)?;
+        notes.push(format!(
+            "Removed the information for '{old_description}', no record uses it anymore."
+        ));
+    }
+    Ok(notes)
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use diesel::PgConnection;
+
+use crate::{
+    confirm,
+    database::db,
+    standardize::{StandardizationRule, Standardizer},
+};
+
+/// Run a candidate rule against every stored raw description before it is added. Lists what it
+/// captures, what those descriptions are stored as now and would become, and which existing rules
+/// it competes with. The rule is only saved once confirmed.
+pub fn test_rule(
+    connection: &mut PgConnection,
+    standardizer: &Standardizer,
+    rule: StandardizationRule,
+    rules_path: &Path,
+) {
+    let described = rule.to_string();
+    let candidate = match standardizer.with_rule(rule) {
+        Ok(candidate) => candidate,
+        Err(e) => {
+            println!("The rule {described} is not valid. Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let new_rule = candidate.rules().last().unwrap();
+
+    let raw_descriptions = match db::select_raw_descriptions(connection) {
+        Ok(raw_descriptions) => raw_descriptions,
+        Err(e) => panic!("There was an error loading the raw descriptions. Error: {e}"),
+    };
+
+    println!("Testing rule {described}");
+    let (mut matched, mut matched_records, mut changed, mut collisions) = (0, 0, 0, 0);
+    for (raw_description, stored, count) in &raw_descriptions {
+        let matches = candidate.matches(raw_description);
+        let Some(own) = matches
+            .iter()
+            .find(|found| std::ptr::eq(found.rule, new_rule))
+        else {
+            continue;
+        };
+        matched += 1;
+        matched_records += count;
+
+        let produced = candidate.standardize(raw_description);
+        if produced != *stored {
+            changed += 1;
+        }
+        println!("{raw_description} ({count} record(s))");
+        println!("    stored: {stored} | would be: {produced}");
+
+        // Existing rules matching the same description compete with the new one
+        let colliding: Vec<&StandardizationRule> = matches
+            .iter()
+            .filter(|found| {
+                !std::ptr::eq(found.rule, new_rule) && found.standardized != own.standardized
+            })
+            .map(|found| found.rule)
+            .collect();
+        collisions += colliding.len();
+        match candidate.best_match(raw_description) {
+            Some(winner) if std::ptr::eq(winner.rule, new_rule) => {
+                for other in colliding {
+                    println!("    collision: overrides {other}");
+                }
+            }
+            Some(winner) if !colliding.is_empty() => {
+                println!("    collision: loses to {}", winner.rule)
+            }
+            _ => (),
+        }
+    }
+
+    if matched == 0 {
+        println!("The rule matches none of the stored descriptions.");
+    } else {
+        println!(
+            "Matched {matched} raw description(s) across {matched_records} record(s). {changed} would change and {collisions} collide with existing rules."
+        );
+    }
+
+    if !confirm("Add this rule?") {
+        println!("The rule was not added.");
+        return;
+    }
+    match candidate.save(rules_path) {
+        Ok(_) => println!(
+            "Added rule {described} to {:?}. Run 'budget restandardize' to apply it to stored records.",
+            rules_path
+        ),
+        Err(e) => panic!("There was an error saving the standardization rules. Error: {e}"),
+    }
+}
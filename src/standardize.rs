@@ -0,0 +1,788 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    path::Path,
+    sync::LazyLock,
+};
+
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+use crate::location;
+
+// The rule set used when no rules file exists. Mostly purchases that contain a UUID in them that I want to standardize
+const DEFAULT_RULES: &[(&str, &str)] = &[
+    // Online Stores
+    ("amazon", "amazon"),
+    ("amzn", "amazon"),
+    ("prime video", "tv"),
+    ("amc", "amc"),
+    ("petsmart", "petsmart"),
+    // In Person Stores
+    ("target", "target"),
+    ("the home depot", "home depot"),
+    ("rei", "rei"),
+    ("barnes & noble", "barnes & noble"),
+    ("autozone", "autozone"),
+    ("crate & barrel", "crate & barrel"),
+    ("vca animal hosp", "vca veterinarian"),
+    ("laz parking", "laz parking"),
+    ("spothero", "spothero"),
+    ("walgreens", "walgreens"),
+    ("831 bowlero", "bowlero"),
+    // Airlines & Travel
+    ("united", "united airlines"),
+    ("delta", "delta airlines"),
+    ("hilton", "hilton"),
+    ("airbnb", "airbnb"),
+    // Restaurants
+    ("ihop", "ihop"),
+    ("bonefish", "bonefish"),
+    ("chick-fil-a", "chick-fil-a"),
+    ("chipotle", "chipotle"),
+    ("mad greens", "mad greens"),
+    ("domino's", "dominos"),
+    ("dunkin", "dunkin donuts"),
+    ("panda express", "panda express"),
+    ("noodles & co", "noodles & co"),
+    ("olive garden", "olive garden"),
+    ("oracl*waffle house", "waffle house"),
+    ("bop & gogi", "bop & gogi"),
+    ("paypal *domino's", "dominos"),
+    // Gas
+    ("safeway fuel", "safeway fuel"),
+    ("king soopers fuel", "king soopers fuel"),
+    ("conoco", "conoco"),
+    ("phillips 66", "phillips 66"),
+    ("stop 4 gas", "stop 4 gas"),
+    ("circle k", "circle k"),
+    ("shell", "shell"),
+    ("7-eleven", "7-eleven"),
+    ("qt", "quicktrip"),
+    ("chevron", "chevron"),
+    ("kum&go", "kum&go"),
+    // Groceries
+    ("trader joe s", "trader joe's"),
+    ("publix", "publix"),
+    ("safeway #", "safeway"),
+    ("king soopers #", "king soopers"),
+];
+
+// Payment processors that put their own prefix in front of the merchant. These keep the merchant
+// name and sit below every other rule, so a specific merchant rule always wins.
+const DEFAULT_PROCESSOR_RULES: &[(&str, &str)] = &[
+    (r"^(?:sq|tst) ?\*\s*(.+)", "$1"),
+    (r"^paypal ?\*\s*(.+)", "$1"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleKind {
+    Prefix,
+    Contains,
+    Regex,
+}
+
+impl RuleKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind.to_lowercase().as_str() {
+            "prefix" => Some(RuleKind::Prefix),
+            "contains" => Some(RuleKind::Contains),
+            "regex" => Some(RuleKind::Regex),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleKind::Prefix => write!(f, "prefix"),
+            RuleKind::Contains => write!(f, "contains"),
+            RuleKind::Regex => write!(f, "regex"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StandardizationRule {
+    pub kind: RuleKind,
+    pub pattern: String,
+    // For regex rules this may reference capture groups, e.g. "$1" or "${merchant}"
+    pub standardized: String,
+    // Higher priorities win when several rules match. Ties go to the longest match, then file order.
+    pub priority: i64,
+}
+
+impl StandardizationRule {
+    fn to_json(&self) -> serde_json::Value {
+        let mut rule = serde_json::json!({
+            "pattern": self.pattern,
+            "standardized": self.standardized,
+        });
+        if self.kind != RuleKind::Prefix {
+            rule["kind"] = self.kind.to_string().into();
+        }
+        if self.priority != 0 {
+            rule["priority"] = self.priority.into();
+        }
+        rule
+    }
+}
+
+impl fmt::Display for StandardizationRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' -> '{}' (priority {})",
+            self.kind, self.pattern, self.standardized, self.priority
+        )
+    }
+}
+
+// A rule that matched a description, along with what it would produce
+pub struct RuleMatch<'a> {
+    pub rule: &'a StandardizationRule,
+    pub standardized: String,
+    index: usize,
+    length: usize,
+}
+
+impl RuleMatch<'_> {
+    fn beats(&self, other: &RuleMatch) -> bool {
+        (
+            self.rule.priority,
+            self.length,
+            std::cmp::Reverse(self.index),
+        ) > (
+            other.rule.priority,
+            other.length,
+            std::cmp::Reverse(other.index),
+        )
+    }
+}
+
+/// A rule that never applies to its own pattern, since another rule wins with a different result
+pub struct Conflict<'a> {
+    // Indexes into the rules
+    pub loser: usize,
+    pub winner: usize,
+    rule: &'a StandardizationRule,
+    winning_rule: &'a StandardizationRule,
+    standardized: String,
+}
+
+impl fmt::Display for Conflict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (rule, winner) = (self.rule, self.winning_rule);
+        if rule.kind == winner.kind && rule.pattern == winner.pattern {
+            return write!(
+                f,
+                "{} '{}' is defined twice ('{}' and '{}'). {winner} wins.",
+                rule.kind, rule.pattern, winner.standardized, rule.standardized
+            );
+        }
+        write!(
+            f,
+            "{rule} loses to {winner}, which gives '{}' for '{}'.",
+            self.standardized, rule.pattern
+        )
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+pub struct Standardizer {
+    rules: Vec<StandardizationRule>,
+    // Rule indexes keyed by prefix, so prefix matching costs one lookup per character of the description
+    prefixes: HashMap<String, Vec<usize>>,
+    // Every contains rule is searched in a single pass
+    contains: AhoCorasick,
+    contains_rules: Vec<usize>,
+    // The set finds which regex rules apply, the individual regexes then extract their captures
+    regex_set: RegexSet,
+    regexes: Vec<(usize, Regex)>,
+}
+
+impl Standardizer {
+    pub fn new(rules: Vec<StandardizationRule>) -> Result<Self, Box<dyn Error>> {
+        let mut prefixes: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut contains_rules = Vec::new();
+        let mut regexes = Vec::new();
+        for (index, rule) in rules.iter().enumerate() {
+            match rule.kind {
+                RuleKind::Prefix => prefixes
+                    .entry(rule.pattern.clone())
+                    .or_default()
+                    .push(index),
+                RuleKind::Contains => contains_rules.push(index),
+                RuleKind::Regex => regexes.push((index, compile_regex(&rule.pattern)?)),
+            }
+        }
+
+        let contains = AhoCorasick::new(contains_rules.iter().map(|&index| &rules[index].pattern))?;
+        let regex_set =
+            RegexSetBuilder::new(regexes.iter().map(|(index, _)| &rules[*index].pattern))
+                .case_insensitive(true)
+                .build()?;
+
+        Ok(Standardizer {
+            rules,
+            prefixes,
+            contains,
+            contains_rules,
+            regex_set,
+            regexes,
+        })
+    }
+
+    pub fn defaults() -> Self {
+        let merchants = DEFAULT_RULES
+            .iter()
+            .map(|(pattern, standardized)| StandardizationRule {
+                kind: RuleKind::Prefix,
+                pattern: pattern.to_string(),
+                standardized: standardized.to_string(),
+                priority: 0,
+            });
+        let processors = DEFAULT_PROCESSOR_RULES
+            .iter()
+            .map(|(pattern, standardized)| StandardizationRule {
+                kind: RuleKind::Regex,
+                pattern: pattern.to_string(),
+                standardized: standardized.to_string(),
+                priority: -1,
+            });
+        Standardizer::new(merchants.chain(processors).collect())
+            .expect("The default standardization rules must be valid")
+    }
+
+    pub fn rules(&self) -> &[StandardizationRule] {
+        &self.rules
+    }
+
+    /// Load the rules file, falling back to the built in rule set when it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Standardizer::defaults());
+        }
+
+        let config: serde_json::Value = serde_json::from_reader(File::open(path)?)?;
+        let Some(raw_rules) = config["rules"].as_array() else {
+            return Err(format!(
+                "Standardization rules file {:?} must contain a 'rules' array",
+                path
+            )
+            .into());
+        };
+
+        let mut rules = Vec::with_capacity(raw_rules.len());
+        for raw_rule in raw_rules {
+            let (Some(pattern), Some(standardized)) = (
+                raw_rule["pattern"].as_str(),
+                raw_rule["standardized"].as_str(),
+            ) else {
+                return Err(format!(
+                    "Every standardization rule in {:?} needs a 'pattern' and a 'standardized' value. Rule: {raw_rule}",
+                    path
+                )
+                .into());
+            };
+            let kind = match raw_rule["kind"].as_str() {
+                None => RuleKind::Prefix,
+                Some(kind) => RuleKind::parse(kind).ok_or_else(|| {
+                    format!(
+                        "Rule kinds in {:?} must be 'prefix', 'contains' or 'regex'. Rule: {raw_rule}",
+                        path
+                    )
+                })?,
+            };
+            let priority = match &raw_rule["priority"] {
+                serde_json::Value::Null => 0,
+                value => value.as_i64().ok_or_else(|| {
+                    format!(
+                        "Rule priorities in {:?} must be integers. Rule: {raw_rule}",
+                        path
+                    )
+                })?,
+            };
+            rules.push(StandardizationRule {
+                kind,
+                // Descriptions are matched in lowercase. Regexes are case insensitive instead,
+                // since lowercasing would change escapes such as \S or \D.
+                pattern: match kind {
+                    RuleKind::Regex => pattern.to_string(),
+                    _ => pattern.to_lowercase(),
+                },
+                standardized: standardized.to_string(),
+                priority,
+            });
+        }
+
+        Standardizer::new(rules)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let rules: Vec<serde_json::Value> = self
+            .rules
+            .iter()
+            .map(StandardizationRule::to_json)
+            .collect();
+        let contents = serde_json::to_string_pretty(&serde_json::json!({ "rules": rules }))?;
+        fs::write(path, contents + "\n")?;
+        Ok(())
+    }
+
+    /// Rebuild the matcher with one more rule, failing if the rule is invalid
+    pub fn with_rule(&self, rule: StandardizationRule) -> Result<Self, Box<dyn Error>> {
+        let mut rules = self.rules.clone();
+        rules.push(rule);
+        Standardizer::new(rules)
+    }
+
+    /// Every rule that loses to a rule with a different result on its own pattern. Regex rules
+    /// have no literal pattern to try, so they are only found as the winner.
+    pub fn conflicts(&self) -> Vec<Conflict<'_>> {
+        let mut conflicts = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.kind == RuleKind::Regex {
+                continue;
+            }
+            let Some(best) = self.best_match(&rule.pattern) else {
+                continue;
+            };
+            if best.index != index && best.standardized != rule.standardized {
+                conflicts.push(Conflict {
+                    loser: index,
+                    winner: best.index,
+                    rule,
+                    winning_rule: best.rule,
+                    standardized: best.standardized,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Every rule that matches the description, in no particular order
+    pub fn matches(&self, description: &str) -> Vec<RuleMatch<'_>> {
+        let raw_description = description.to_lowercase();
+        let mut matches = Vec::new();
+
+        let ends = raw_description
+            .char_indices()
+            .skip(1)
+            .map(|(end, _)| end)
+            .chain([raw_description.len()]);
+        for end in ends {
+            for &index in self
+                .prefixes
+                .get(&raw_description[..end])
+                .into_iter()
+                .flatten()
+            {
+                let rule = &self.rules[index];
+                matches.push(RuleMatch {
+                    rule,
+                    standardized: rule.standardized.clone(),
+                    index,
+                    length: end,
+                });
+            }
+        }
+
+        for found in self.contains.find_overlapping_iter(&raw_description) {
+            let index = self.contains_rules[found.pattern().as_usize()];
+            // Overlapping search reports every occurrence, one match per rule is enough
+            if matches.iter().any(|m: &RuleMatch| m.index == index) {
+                continue;
+            }
+            let rule = &self.rules[index];
+            matches.push(RuleMatch {
+                rule,
+                standardized: rule.standardized.clone(),
+                index,
+                length: found.len(),
+            });
+        }
+
+        for set_index in self.regex_set.matches(&raw_description).iter() {
+            let (index, regex) = &self.regexes[set_index];
+            let Some(captures) = regex.captures(&raw_description) else {
+                continue;
+            };
+            let rule = &self.rules[*index];
+            let mut standardized = String::new();
+            captures.expand(&rule.standardized, &mut standardized);
+            matches.push(RuleMatch {
+                rule,
+                standardized: standardized
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+                index: *index,
+                length: captures.get(0).map_or(0, |m| m.len()),
+            });
+        }
+
+        matches
+    }
+
+    /// The match that decides the standardized description, if any rule applies
+    pub fn best_match(&self, description: &str) -> Option<RuleMatch<'_>> {
+        self.matches(description)
+            .into_iter()
+            .reduce(|best, candidate| {
+                if candidate.beats(&best) {
+                    candidate
+                } else {
+                    best
+                }
+            })
+    }
+
+    pub fn standardize(&self, description: &str) -> String {
+        match self.best_match(description) {
+            // A bare capture holds whatever followed the processor prefix, store number and city
+            // included, so its noise is stripped to collapse every store of the merchant into one.
+            // Any other output is kept as the rule wrote it.
+            Some(found)
+                if found.rule.kind == RuleKind::Regex
+                    && is_bare_group(&found.rule.standardized) =>
+            {
+                strip_noise(&found.standardized)
+            }
+            Some(found) if !found.standardized.is_empty() => found.standardized,
+            _ => strip_noise(description),
+        }
+    }
+}
+
+// Whether a regex rule's output is a single capture group and nothing else, e.g. "$1" or "${merchant}"
+fn is_bare_group(template: &str) -> bool {
+    static BARE_GROUP: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*\$(?:\w+|\{\w+\})\s*$").expect("The bare group pattern must be valid")
+    });
+    BARE_GROUP.is_match(template)
+}
+
+static NOISE_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // Phone numbers
+        r"\(?\b\d{3}\)?[-. ]?\d{3}[-. ]\d{4}\b",
+        // Dates such as 06/14, 06/14/25 or 2025-06-14
+        r"\b\d{1,2}/\d{1,2}(?:/\d{2,4})?\b",
+        r"\b\d{4}-\d{2}-\d{2}\b",
+        // Store numbers such as "#1234" or "store 01234"
+        r"#\s*\d+",
+        r"\b\d{3,}\b",
+        // Transaction ids, i.e. tokens that mix letters and digits like "8h2k1"
+        r"\b(?:[a-z]+\d|\d+[a-z])[a-z0-9]*\b",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("Noise patterns must be valid"))
+    .collect()
+});
+
+// Stands in for removed noise until the description is split into words
+const NOISE_MARK: &str = "\u{e000}";
+
+/// Lowercase the description and remove store numbers, transaction ids, dates and phone numbers,
+/// leaving the words of the merchant name and location. Each word comes with whether noise was
+/// removed right before it, since a location usually follows the store number.
+pub fn noise_free_words(description: &str) -> Vec<(String, bool)> {
+    let mut stripped = description.to_lowercase().replace('*', " ");
+    let mark = format!(" {NOISE_MARK} ");
+    for pattern in NOISE_PATTERNS.iter() {
+        stripped = pattern
+            .replace_all(&stripped, regex::NoExpand(&mark))
+            .into_owned();
+    }
+
+    let mut words = Vec::new();
+    let mut after_noise = false;
+    for word in stripped.split_whitespace() {
+        if word == NOISE_MARK {
+            after_noise = true;
+            continue;
+        }
+        let word = word.trim_matches(|c: char| matches!(c, '-' | '.' | ',' | ':' | '/'));
+        if word.is_empty() {
+            continue;
+        }
+        words.push((word.to_string(), after_noise));
+        after_noise = false;
+    }
+    words
+}
+
+/// Normalize a description no rule matched, so the same unknown merchant collapses into one description
+/// regardless of store numbers, transaction ids, dates, phone numbers or the city it was charged in
+pub fn strip_noise(description: &str) -> String {
+    let noise_free = noise_free_words(description);
+    let words: Vec<&str> = noise_free.iter().map(|(word, _)| word.as_str()).collect();
+    let after_noise: Vec<bool> = noise_free
+        .iter()
+        .map(|(_, after_noise)| *after_noise)
+        .collect();
+    let (merchant_words, _) = location::split_location(&words, &after_noise);
+
+    if merchant_words == 0 {
+        return description
+            .to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+    }
+    words[..merchant_words].join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        kind: RuleKind,
+        pattern: &str,
+        standardized: &str,
+        priority: i64,
+    ) -> StandardizationRule {
+        StandardizationRule {
+            kind,
+            pattern: pattern.to_string(),
+            standardized: standardized.to_string(),
+            priority,
+        }
+    }
+
+    fn found(rule: &StandardizationRule, index: usize, length: usize) -> RuleMatch<'_> {
+        RuleMatch {
+            rule,
+            standardized: rule.standardized.clone(),
+            index,
+            length,
+        }
+    }
+
+    #[test]
+    fn higher_priority_beats_longer_match() {
+        let short = rule(RuleKind::Prefix, "amzn", "amazon", 1);
+        let long = rule(RuleKind::Prefix, "amzn mktp", "marketplace", 0);
+        assert!(found(&short, 0, 4).beats(&found(&long, 1, 9)));
+        assert!(!found(&long, 1, 9).beats(&found(&short, 0, 4)));
+    }
+
+    #[test]
+    fn longer_match_beats_shorter_at_equal_priority() {
+        let short = rule(RuleKind::Prefix, "safeway", "safeway", 0);
+        let long = rule(RuleKind::Prefix, "safeway fuel", "safeway fuel", 0);
+        assert!(found(&long, 1, 12).beats(&found(&short, 0, 7)));
+    }
+
+    #[test]
+    fn earlier_rule_wins_a_tie() {
+        let first = rule(RuleKind::Prefix, "shell", "shell", 0);
+        let second = rule(RuleKind::Prefix, "shell", "shell oil", 0);
+        assert!(found(&first, 0, 5).beats(&found(&second, 1, 5)));
+        assert!(!found(&second, 1, 5).beats(&found(&first, 0, 5)));
+    }
+
+    #[test]
+    fn prefix_rules_standardize_by_longest_match() {
+        let standardizer = Standardizer::defaults();
+        assert_eq!(
+            standardizer.standardize("SAFEWAY FUEL 1234"),
+            "safeway fuel"
+        );
+        assert_eq!(
+            standardizer.standardize("SAFEWAY #1234 DENVER CO"),
+            "safeway"
+        );
+    }
+
+    #[test]
+    fn merchant_rule_beats_processor_rule() {
+        let standardizer = Standardizer::defaults();
+        assert_eq!(standardizer.standardize("PAYPAL *DOMINO'S 8821"), "dominos");
+    }
+
+    #[test]
+    fn processor_capture_loses_its_store_number_and_location() {
+        let standardizer = Standardizer::defaults();
+        assert_eq!(
+            standardizer.standardize("CHIPOTLE 1234 DENVER CO"),
+            "chipotle"
+        );
+        assert_eq!(
+            standardizer.standardize("SQ *CHIPOTLE 0981 BOULDER CO"),
+            "chipotle"
+        );
+    }
+
+    #[test]
+    fn processor_capture_is_not_matched_against_merchant_rules() {
+        let standardizer = Standardizer::defaults();
+        assert_eq!(
+            standardizer.standardize("SQ *UNITED FLOWERS 0012"),
+            "united flowers"
+        );
+    }
+
+    #[test]
+    fn templates_with_literal_text_are_kept_as_written() {
+        let mut rules: Vec<StandardizationRule> = Standardizer::defaults().rules().to_vec();
+        rules.push(rule(
+            RuleKind::Regex,
+            r"^amazon (fresh|prime)",
+            "amazon $1",
+            5,
+        ));
+        let standardizer = Standardizer::new(rules).unwrap();
+        assert_eq!(
+            standardizer.standardize("AMAZON FRESH 1234"),
+            "amazon fresh"
+        );
+        assert_eq!(
+            standardizer.standardize("AMAZON PRIME*8H2K1"),
+            "amazon prime"
+        );
+    }
+
+    #[test]
+    fn bare_groups() {
+        assert!(is_bare_group("$1"));
+        assert!(is_bare_group("${merchant}"));
+        assert!(!is_bare_group("amazon $1"));
+        assert!(!is_bare_group("$1 $2"));
+        assert!(!is_bare_group("shell"));
+    }
+
+    #[test]
+    fn processor_capture_is_stripped_of_noise() {
+        let standardizer = Standardizer::defaults();
+        assert_eq!(
+            standardizer.standardize("SQ *BLUE BOTTLE 0042 DENVER CO"),
+            "blue bottle"
+        );
+        assert_eq!(
+            standardizer.standardize("TST* BLUE BOTTLE 7781 FORT COLLINS CO"),
+            "blue bottle"
+        );
+    }
+
+    #[test]
+    fn literal_regex_output_is_kept() {
+        let standardizer = Standardizer::new(vec![rule(
+            RuleKind::Regex,
+            r"^apl\*\s*itunes",
+            "apple 1234",
+            0,
+        )])
+        .unwrap();
+        assert_eq!(
+            standardizer.standardize("APL* ITUNES.COM/BILL"),
+            "apple 1234"
+        );
+    }
+
+    #[test]
+    fn contains_rules_match_anywhere() {
+        let standardizer =
+            Standardizer::new(vec![rule(RuleKind::Contains, "netflix", "netflix", 0)]).unwrap();
+        assert_eq!(
+            standardizer.standardize("RECURRING NETFLIX.COM 866"),
+            "netflix"
+        );
+    }
+
+    #[test]
+    fn unmatched_descriptions_lose_store_numbers_and_location() {
+        let standardizer = Standardizer::new(Vec::new()).unwrap();
+        assert_eq!(
+            standardizer.standardize("WHOLE FOODS #10234 BOULDER CO"),
+            "whole foods"
+        );
+    }
+
+    #[test]
+    fn merchants_ending_in_a_state_code_are_kept_whole() {
+        let standardizer = Standardizer::new(Vec::new()).unwrap();
+        for (description, expected) in [
+            ("ACE HARDWARE CO", "ace hardware co"),
+            ("XCEL ENERGY CO", "xcel energy co"),
+            ("ROCKY MOUNTAIN CHOCOLATE CO", "rocky mountain chocolate co"),
+            ("PANERA BREAD OR", "panera bread or"),
+        ] {
+            assert_eq!(standardizer.standardize(description), expected);
+        }
+    }
+
+    #[test]
+    fn known_cities_are_stripped_without_a_store_number() {
+        let standardizer = Standardizer::new(Vec::new()).unwrap();
+        assert_eq!(
+            standardizer.standardize("KING SOOPERS DENVER CO"),
+            "king soopers"
+        );
+        // A single merchant word needs a store number before the city
+        assert_eq!(
+            standardizer.standardize("STARBUCKS DENVER CO"),
+            "starbucks denver co"
+        );
+        assert_eq!(
+            standardizer.standardize("CHIPOTLE 1234 DENVER CO"),
+            "chipotle"
+        );
+    }
+
+    fn conflicts(rules: Vec<StandardizationRule>) -> Vec<(usize, usize)> {
+        let standardizer = Standardizer::new(rules).unwrap();
+        standardizer
+            .conflicts()
+            .iter()
+            .map(|conflict| (conflict.loser, conflict.winner))
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_across_rule_kinds() {
+        // A higher priority contains rule takes every description of the prefix rule
+        assert_eq!(
+            conflicts(vec![
+                rule(RuleKind::Prefix, "amazon mktp", "amazon marketplace", 0),
+                rule(RuleKind::Contains, "amazon", "amazon", 1),
+            ]),
+            vec![(0, 1)]
+        );
+        // So does a higher priority regex
+        assert_eq!(
+            conflicts(vec![
+                rule(RuleKind::Contains, "uber eats", "uber", 0),
+                rule(RuleKind::Regex, r"^uber eats", "uber eats", 1),
+            ]),
+            vec![(0, 1)]
+        );
+        // The same pattern twice, the first definition wins
+        assert_eq!(
+            conflicts(vec![
+                rule(RuleKind::Prefix, "shell", "shell", 0),
+                rule(RuleKind::Prefix, "shell", "gas", 0),
+            ]),
+            vec![(1, 0)]
+        );
+    }
+
+    #[test]
+    fn exceptions_and_agreeing_rules_are_not_conflicts() {
+        assert!(conflicts(vec![
+            rule(RuleKind::Contains, "amazon", "amazon", 0),
+            rule(RuleKind::Contains, "amazon prime", "amazon prime", 5),
+            rule(RuleKind::Prefix, "amzn", "amazon", 1),
+            rule(RuleKind::Contains, "amzn mktp", "amazon", 2),
+        ])
+        .is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use crate::UploadDescription;
+
+// Only descriptions at least this similar are worth suggesting
+const MINIMUM_SIMILARITY: f64 = 0.2;
+
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Sørensen–Dice coefficient over character bigrams, which tolerates small spelling differences
+pub fn bigram_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let mut remaining = b.clone();
+    let mut shared = 0;
+    for bigram in &a {
+        if let Some(position) = remaining.iter().position(|other| other == bigram) {
+            remaining.swap_remove(position);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+// Share of words in common, so "king soopers fuel" is close to "safeway fuel"
+fn word_similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    (bigram_similarity(&a, &b) + word_similarity(&a, &b)) / 2.0
+}
+
+/// The tagged descriptions closest to `description`, most similar first
+pub fn closest<'a>(
+    description: &str,
+    tagged: &'a [UploadDescription],
+    limit: usize,
+) -> Vec<&'a UploadDescription> {
+    let mut scored: Vec<(f64, &UploadDescription)> = tagged
+        .iter()
+        .filter(|other| other.description != description)
+        .map(|other| (similarity(description, &other.description), other))
+        .filter(|(score, _)| *score >= MINIMUM_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1.description.cmp(&b.1.description))
+    });
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, other)| other)
+        .collect()
+}
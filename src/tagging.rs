@@ -0,0 +1,606 @@
+use std::{
+    collections::HashMap,
+    io::{self, Stdout, Write},
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers,
+    },
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{
+        self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+
+use crate::{
+    classifier::CategoryClassifier,
+    completion::Completer,
+    database::models::DescriptionSpend,
+    line_editor::{self, LineEditor},
+    suggestions, UploadDescription, MAX_SUGGESTIONS,
+};
+use unicode_width::UnicodeWidthStr;
+
+const FIELD_NAMES: [&str; 4] = ["Primary", "Secondary", "Tertiary", "Additional"];
+
+const HELP: &str = "Up/Down: description | Tab: complete or next field | Enter: next field | Ctrl+P/N: field history | 1-3 on an empty primary: use suggestion | Ctrl+S: save | Esc: quit";
+
+struct Entry {
+    description: String,
+    fields: [String; 4],
+    // The values the entry started with, so only changed entries are saved
+    original: [String; 4],
+    // Whether the classifier has already learnt from this entry's tags
+    learned: bool,
+}
+
+impl Entry {
+    fn is_tagged(&self) -> bool {
+        !self.fields[0].trim().is_empty()
+    }
+
+    fn is_changed(&self) -> bool {
+        self.is_tagged() && self.fields != self.original
+    }
+
+    fn to_upload(&self) -> UploadDescription {
+        let [primary, secondary, tertiary, additional] = self.fields.clone();
+        UploadDescription {
+            description: self.description.clone(),
+            primary_information: primary,
+            secondary_information: secondary,
+            tertiary_information: tertiary,
+            additional_information: additional,
+            category_id: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Pending {
+    Save,
+    Quit,
+}
+
+struct TaggingScreen<'a> {
+    title: &'a str,
+    entries: Vec<Entry>,
+    selected: usize,
+    field: usize,
+    // Edits the focused field, whose value is copied back into the entry after every change
+    editor: LineEditor,
+    tagged: &'a [UploadDescription],
+    classifier: &'a mut CategoryClassifier,
+    spend: &'a HashMap<String, DescriptionSpend>,
+    // An action waiting for a y/n answer
+    pending: Option<Pending>,
+}
+
+// Cut text down to the given number of columns
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+impl TaggingScreen<'_> {
+    fn tagged_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.is_tagged())
+            .count()
+    }
+
+    fn changed_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.is_changed())
+            .count()
+    }
+
+    // Descriptions tagged before and during this session, to suggest from
+    fn suggestion_pool(&self) -> Vec<UploadDescription> {
+        self.tagged
+            .iter()
+            .cloned()
+            .chain(
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.is_tagged())
+                    .map(Entry::to_upload),
+            )
+            .collect()
+    }
+
+    fn current_suggestions(&self) -> Vec<UploadDescription> {
+        let pool = self.suggestion_pool();
+        let description = &self.entries[self.selected].description;
+        suggestions::closest(description, &pool, MAX_SUGGESTIONS)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    // Values the field already has elsewhere, leaving out the entry being edited
+    fn completer(&self, field: usize) -> Completer {
+        let tagged = self.tagged.iter().map(|description| match field {
+            0 => description.primary_information.as_str(),
+            1 => description.secondary_information.as_str(),
+            2 => description.tertiary_information.as_str(),
+            _ => description.additional_information.as_str(),
+        });
+        let session = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.selected)
+            .map(|(_, entry)| entry.fields[field].as_str());
+        Completer::new(tagged.chain(session))
+    }
+
+    fn focus(&mut self, field: usize) {
+        self.field = field;
+        self.editor = LineEditor::new(&self.entries[self.selected].fields[field]);
+    }
+
+    fn set_field(&mut self, value: &str) {
+        self.editor.set_text(value);
+        self.sync();
+    }
+
+    fn select(&mut self, index: usize) {
+        let entry = &mut self.entries[self.selected];
+        if entry.is_tagged() && !entry.learned {
+            self.classifier
+                .learn(&entry.description, &entry.fields[0], &entry.fields[1]);
+            entry.learned = true;
+        }
+        for (name, value) in FIELD_NAMES.iter().zip(&entry.fields) {
+            line_editor::remember(name, value);
+        }
+        self.selected = index.min(self.entries.len() - 1);
+        self.focus(0);
+    }
+
+    fn draw(&self, out: &mut Stdout) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        queue!(out, Hide, Clear(ClearType::All), MoveTo(0, 0))?;
+
+        // Progress across the whole batch
+        let tagged = self.tagged_count();
+        let total = self.entries.len();
+        let bar_width = 20;
+        let filled = tagged * bar_width / total.max(1);
+        queue!(
+            out,
+            Print(fit(
+                &format!(
+                    "{} [{}{}] {tagged}/{total} tagged",
+                    self.title,
+                    "#".repeat(filled),
+                    "-".repeat(bar_width - filled)
+                ),
+                width
+            ))
+        )?;
+
+        // Pending descriptions on the left, scrolled so the selection stays visible
+        let list_width = (width / 3).clamp(10, 40);
+        let list_rows = height.saturating_sub(4).max(1);
+        let first = (self.selected + 1).saturating_sub(list_rows);
+        for (row, (index, entry)) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(list_rows)
+            .enumerate()
+        {
+            let marker = if entry.is_tagged() { "[x]" } else { "[ ]" };
+            let total = self
+                .spend
+                .get(&entry.description)
+                .map_or(0.0, |spend| spend.total);
+            let line = fit(
+                &format!("{marker} {total:>10.2} {}", entry.description),
+                list_width.saturating_sub(1),
+            );
+            queue!(out, MoveTo(0, (row + 2) as u16))?;
+            if index == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+
+        // The selected description and its form on the right
+        let x = list_width as u16 + 1;
+        let form_width = width.saturating_sub(list_width + 1);
+        let entry = &self.entries[self.selected];
+        let mut row = 2;
+        let line = |out: &mut Stdout, row: &mut u16, text: String| -> io::Result<()> {
+            queue!(out, MoveTo(x, *row), Print(fit(&text, form_width)))?;
+            *row += 1;
+            Ok(())
+        };
+
+        line(out, &mut row, format!("Description: {}", entry.description))?;
+        if let Some(spend) = self.spend.get(&entry.description) {
+            line(
+                out,
+                &mut row,
+                format!(
+                    "{} transaction(s) | total {:.2} | average {:.2}",
+                    spend.transactions, spend.total, spend.average
+                ),
+            )?;
+            line(
+                out,
+                &mut row,
+                format!(
+                    "{} to {} | accounts: {}",
+                    spend.first_date,
+                    spend.last_date,
+                    spend.accounts.join(", ")
+                ),
+            )?;
+            line(
+                out,
+                &mut row,
+                format!("Bank descriptions: {}", spend.raw_descriptions.join(" | ")),
+            )?;
+        }
+        let (primary, secondary) = self.classifier.predict(&entry.description);
+        if let Some(primary) = primary {
+            let mut prediction = format!(
+                "Predicted: {} ({:.0}%)",
+                primary.label,
+                primary.confidence * 100.0
+            );
+            if let Some(secondary) = secondary {
+                prediction += &format!(
+                    " > {} ({:.0}%)",
+                    secondary.label,
+                    secondary.confidence * 100.0
+                );
+            }
+            line(out, &mut row, prediction)?;
+        }
+        line(out, &mut row, String::new())?;
+
+        let suggestions = self.current_suggestions();
+        if !suggestions.is_empty() {
+            line(out, &mut row, "Similar tagged descriptions:".to_string())?;
+            for (index, suggestion) in suggestions.iter().enumerate() {
+                line(
+                    out,
+                    &mut row,
+                    format!(
+                        "  {}) {}: {} > {} > {} ({})",
+                        index + 1,
+                        suggestion.description,
+                        suggestion.primary_information,
+                        suggestion.secondary_information,
+                        suggestion.tertiary_information,
+                        suggestion.additional_information
+                    ),
+                )?;
+            }
+            line(out, &mut row, String::new())?;
+        }
+
+        let label_width = FIELD_NAMES.iter().map(|name| name.len()).max().unwrap() + 2;
+        let mut cursor = (x, row);
+        let mut warnings = Vec::new();
+        for (index, name) in FIELD_NAMES.iter().enumerate() {
+            let label = format!("{:<label_width$}", format!("{name}:"));
+            let value = &entry.fields[index];
+            let completer = self.completer(index);
+            if index == self.field {
+                cursor = (x + (label.len() + self.editor.cursor_width()) as u16, row);
+                // The rest of the completion, dimmed after what has been typed
+                let hint = completer.hint(value).filter(|_| self.editor.at_end());
+                if let Some(hint) = hint {
+                    let column = label.len() + value.width();
+                    queue!(
+                        out,
+                        MoveTo((x as usize + column) as u16, row),
+                        SetAttribute(Attribute::Dim),
+                        Print(fit(&hint, form_width.saturating_sub(column))),
+                        SetAttribute(Attribute::Reset)
+                    )?;
+                }
+            }
+            if let Some(existing) = completer.close_to(value) {
+                warnings.push(format!(
+                    "Warning: {name} '{}' is close to the existing '{existing}'",
+                    value.trim()
+                ));
+            }
+            line(out, &mut row, format!("{label}{value}"))?;
+        }
+        if !warnings.is_empty() {
+            line(out, &mut row, String::new())?;
+            for warning in warnings {
+                line(out, &mut row, warning)?;
+            }
+        }
+
+        let status = match self.pending {
+            Some(Pending::Save) => format!(
+                "Save {} changed description(s)? (y/n)",
+                self.changed_count()
+            ),
+            Some(Pending::Quit) => format!(
+                "Quit and discard {} changed description(s)? (y/n)",
+                self.changed_count()
+            ),
+            None => HELP.to_string(),
+        };
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1) as u16),
+            Print(fit(&status, width))
+        )?;
+
+        let (cursor_x, cursor_y) = cursor;
+        queue!(
+            out,
+            MoveTo(cursor_x.min((width as u16).saturating_sub(1)), cursor_y),
+            Show
+        )?;
+        out.flush()
+    }
+
+    // Handle a key press. Returns whether the session is over, and if so whether to save.
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<bool> {
+        if let Some(pending) = self.pending.take() {
+            if matches!(code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                return Some(matches!(pending, Pending::Save));
+            }
+            return None;
+        }
+
+        let last = self.entries.len() - 1;
+        match (code, modifiers) {
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => self.pending = Some(Pending::Save),
+            (KeyCode::Esc, _) => self.pending = Some(Pending::Quit),
+            (KeyCode::Up, _) => self.select(self.selected.saturating_sub(1)),
+            (KeyCode::Down, _) => self.select(self.selected + 1),
+            (KeyCode::PageUp, _) => self.select(self.selected.saturating_sub(10)),
+            (KeyCode::PageDown, _) => self.select(self.selected + 10),
+            (KeyCode::Tab, _) => {
+                let completer = self.completer(self.field);
+                match completer.complete(self.editor.text()) {
+                    Some(completion) => self.set_field(completion),
+                    None => self.focus((self.field + 1) % FIELD_NAMES.len()),
+                }
+            }
+            (KeyCode::BackTab, _) => {
+                self.focus((self.field + FIELD_NAMES.len() - 1) % FIELD_NAMES.len())
+            }
+            (KeyCode::Enter, _) => {
+                if self.field + 1 < FIELD_NAMES.len() {
+                    self.focus(self.field + 1);
+                } else if self.selected < last {
+                    self.select(self.selected + 1);
+                }
+            }
+            (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                self.editor
+                    .history_previous(&line_editor::history(FIELD_NAMES[self.field]));
+                self.sync();
+            }
+            (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                self.editor
+                    .history_next(&line_editor::history(FIELD_NAMES[self.field]));
+                self.sync();
+            }
+            (KeyCode::Char(c), _)
+                if self.field == 0 && self.entries[self.selected].fields[0].is_empty() =>
+            {
+                let suggestions = self.current_suggestions();
+                match c.to_digit(10).map(|d| d as usize) {
+                    Some(number) if (1..=suggestions.len()).contains(&number) => {
+                        let suggestion = &suggestions[number - 1];
+                        self.entries[self.selected].fields = [
+                            suggestion.primary_information.clone(),
+                            suggestion.secondary_information.clone(),
+                            suggestion.tertiary_information.clone(),
+                            suggestion.additional_information.clone(),
+                        ];
+                        if self.selected < last {
+                            self.select(self.selected + 1);
+                        } else {
+                            self.focus(0);
+                        }
+                    }
+                    _ => self.edit(code, modifiers),
+                }
+            }
+            _ => self.edit(code, modifiers),
+        }
+        None
+    }
+
+    // Copy the editor's text back into the focused field
+    fn sync(&mut self) {
+        self.entries[self.selected].fields[self.field] = self.editor.text().to_string();
+    }
+
+    fn edit(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if self.editor.handle_key(code, modifiers) {
+            self.sync();
+        }
+    }
+
+    fn paste(&mut self, text: &str) {
+        self.editor.insert(text);
+        self.sync();
+    }
+
+    fn run(&mut self, out: &mut Stdout) -> io::Result<bool> {
+        loop {
+            self.draw(out)?;
+            match event::read()? {
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                    state: _,
+                }) => {
+                    if let Some(save) = self.handle_key(code, modifiers) {
+                        return Ok(save);
+                    }
+                }
+                Event::Paste(text) if self.pending.is_none() => self.paste(&text),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Order descriptions by spend, the largest spending first. Descriptions that net income come
+/// after all of them, largest first, and descriptions without records come last.
+pub fn sort_by_spend(
+    descriptions: &mut [UploadDescription],
+    spend: &HashMap<String, DescriptionSpend>,
+) {
+    // Spending amounts are negative
+    let rank = |description: &UploadDescription| match spend.get(&description.description) {
+        Some(spend) if spend.total < 0.0 => (0, -spend.total),
+        Some(spend) => (1, spend.total),
+        None => (2, 0.0),
+    };
+    descriptions.sort_by(|a, b| {
+        let (a_group, a_amount) = rank(a);
+        let (b_group, b_amount) = rank(b);
+        a_group
+            .cmp(&b_group)
+            .then_with(|| b_amount.total_cmp(&a_amount))
+            .then_with(|| a.description.cmp(&b.description))
+    });
+}
+
+/// Full screen form to tag `descriptions`, starting from their current information. Descriptions
+/// are listed by spend, largest spending first, so the ones that matter most for reports come first.
+/// Nothing is returned unless the user confirms the save, and only changed descriptions with at
+/// least primary information are returned.
+pub fn tag_descriptions(
+    title: &str,
+    mut descriptions: Vec<UploadDescription>,
+    tagged: &[UploadDescription],
+    classifier: &mut CategoryClassifier,
+    spend: &HashMap<String, DescriptionSpend>,
+) -> io::Result<Vec<UploadDescription>> {
+    if descriptions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    sort_by_spend(&mut descriptions, spend);
+
+    let mut screen = TaggingScreen {
+        title,
+        entries: descriptions
+            .into_iter()
+            .map(|description| {
+                let fields = [
+                    description.primary_information,
+                    description.secondary_information,
+                    description.tertiary_information,
+                    description.additional_information,
+                ];
+                Entry {
+                    description: description.description,
+                    original: fields.clone(),
+                    // Descriptions that are already tagged are part of the classifier's training data
+                    learned: !fields[0].trim().is_empty(),
+                    fields,
+                }
+            })
+            .collect(),
+        selected: 0,
+        field: 0,
+        editor: LineEditor::default(),
+        tagged,
+        classifier,
+        spend,
+        pending: None,
+    };
+
+    screen.focus(0);
+
+    let mut out = io::stdout();
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, EnableBracketedPaste)?;
+    let result = screen.run(&mut out);
+    execute!(out, DisableBracketedPaste, LeaveAlternateScreen, Show)?;
+    disable_raw_mode()?;
+
+    if !result? {
+        return Ok(Vec::new());
+    }
+    Ok(screen
+        .entries
+        .iter()
+        .filter(|entry| entry.is_changed())
+        .map(Entry::to_upload)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn spend(total: f64) -> DescriptionSpend {
+        let date = NaiveDate::from_ymd_opt(2025, 6, 14).unwrap();
+        DescriptionSpend {
+            transactions: 1,
+            total,
+            average: total,
+            first_date: date,
+            last_date: date,
+            accounts: Vec::new(),
+            raw_descriptions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn spending_comes_before_income() {
+        let spend = HashMap::from([
+            ("paycheck".to_string(), spend(2000.0)),
+            ("rent".to_string(), spend(-1500.0)),
+            ("coffee".to_string(), spend(-12.5)),
+            ("refund".to_string(), spend(40.0)),
+        ]);
+        let mut descriptions: Vec<UploadDescription> =
+            ["coffee", "paycheck", "no records", "refund", "rent"]
+                .into_iter()
+                .map(|description| UploadDescription {
+                    description: description.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+
+        sort_by_spend(&mut descriptions, &spend);
+        let order: Vec<&str> = descriptions
+            .iter()
+            .map(|description| description.description.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            ["rent", "coffee", "paycheck", "refund", "no records"]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use chrono::Local;
+use diesel::PgConnection;
+
+use crate::{database::db, import_files, ImportOptions};
+
+// Browsers and sync clients write downloads under these names before renaming them into place
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "partial", "crdownload", "download", "tmp"];
+
+// A file's size and modification time. It is fully written once this stops changing.
+type Signature = (u64, SystemTime);
+
+fn log(message: &str) {
+    println!("[{}] {message}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+}
+
+fn is_partial(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    let partial = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| PARTIAL_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    hidden || partial
+}
+
+// The files in `directory` that could be budget files, with their current signature
+fn scan(directory: &Path) -> HashMap<PathBuf, Signature> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log(&format!("Failed to read {:?}. Error: {e}", directory));
+            return HashMap::new();
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !is_partial(&entry.path()))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some((entry.path(), (metadata.len(), metadata.modified().ok()?)))
+        })
+        .collect()
+}
+
+// The open connection, reconnecting when there is none or the last one stopped working
+fn open_connection<'a>(
+    connection: &'a mut Option<PgConnection>,
+    secret_config: &serde_json::Value,
+) -> Option<&'a mut PgConnection> {
+    if let Some(existing) = connection {
+        if !db::is_connected(existing) {
+            log("Lost the connection to the database, reconnecting.");
+            *connection = None;
+        }
+    }
+    if connection.is_none() {
+        match db::connect(secret_config) {
+            Ok(new) => *connection = Some(new),
+            Err(e) => log(e.trim_end()),
+        }
+    }
+    connection.as_mut()
+}
+
+/// Import budget files as they land in `new/` until the process is stopped. A file is imported
+/// once it has kept the same size and modification time for a whole `interval`, so files still
+/// being downloaded or synced are left alone. Nothing is asked: new descriptions wait in the
+/// untagged queue and files that fail stay put until they change. When the database or the
+/// configuration can't be used, the files are tried again on the next check.
+pub fn watch(cwd: &Path, fp: &Path, secret_config: &serde_json::Value, interval: Duration) {
+    let directory = fp.join("new/");
+    if !directory.is_dir() {
+        panic!(
+            "There is no 'new/' directory available at filepath {:?}",
+            fp
+        );
+    }
+    log(&format!(
+        "Watching {:?} every {}s. Press Ctrl+C to stop.",
+        directory,
+        interval.as_secs()
+    ));
+
+    let mut database: Option<PgConnection> = None;
+    let mut previous: HashMap<PathBuf, Signature> = HashMap::new();
+    // Files already handled, so a file that failed or couldn't be removed isn't imported again
+    let mut handled: HashMap<PathBuf, Signature> = HashMap::new();
+    loop {
+        let current = scan(&directory);
+        handled.retain(|path, signature| current.get(path) == Some(signature));
+
+        let mut ready: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, signature)| {
+                signature.0 > 0
+                    && previous.get(*path) == Some(*signature)
+                    && !handled.contains_key(*path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+
+        let connection = match ready.is_empty() {
+            true => None,
+            false => open_connection(&mut database, secret_config),
+        };
+        if let Some(connection) = connection {
+            for path in &ready {
+                log(&format!("Importing {:?}", path));
+            }
+            let options = ImportOptions {
+                interactive: false,
+                dry_run: false,
+                format: None,
+                remove: true,
+            };
+            let outcome = match import_files(cwd, connection, &ready, &options) {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    log(&format!(
+                        "{e}. The files are tried again on the next check."
+                    ));
+                    previous = current;
+                    thread::sleep(interval);
+                    continue;
+                }
+            };
+            log(&format!(
+                "Imported {} record(s) from {} file(s). {} file(s) failed and stay in {:?}.",
+                outcome.records,
+                outcome.imported.len(),
+                outcome.failed.len(),
+                directory
+            ));
+            for path in &outcome.failed {
+                log(&format!("Failed to import {:?}", path));
+            }
+            for path in ready {
+                if let Some(signature) = current.get(&path) {
+                    handled.insert(path, *signature);
+                }
+            }
+        }
+
+        previous = current;
+        thread::sleep(interval);
+    }
+}